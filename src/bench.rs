@@ -0,0 +1,48 @@
+use crate::field::FieldElement;
+use crate::hash_functions::HashFunction;
+use crate::merkle_tree::MerkleTree;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Inputs come from a fixed seed, so repeated runs hash exactly the same values
+const SEED: u64 = 0x6d696e695f7a6b;
+
+/// Time `hasher` takes to hash `n` random pairs. The pairs are drawn before the clock starts,
+/// so only hashing is measured.
+pub fn hash_throughput(hasher: &dyn HashFunction, n: usize) -> Duration {
+    let values = random_values(2 * n);
+
+    let start = Instant::now();
+    for pair in values.chunks_exact(2) {
+        black_box(hasher.hash(&pair[0], &pair[1]));
+    }
+    start.elapsed()
+}
+
+/// Time `MerkleTree::new` (Poseidon) takes to build a tree over `n_leaves` random leaves
+pub fn merkle_build_time(n_leaves: usize) -> Duration {
+    let leaves = random_values(n_leaves);
+
+    let start = Instant::now();
+    black_box(MerkleTree::new(leaves));
+    start.elapsed()
+}
+
+fn random_values(n: usize) -> Vec<FieldElement> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    (0..n).map(|_| FieldElement::random(&mut rng)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_functions::PoseidonHash;
+
+    #[test]
+    fn smoke_run_over_a_thousand_inputs_takes_measurable_time() {
+        assert!(hash_throughput(&PoseidonHash::new(), 1000) > Duration::ZERO);
+        assert!(merkle_build_time(1000) > Duration::ZERO);
+    }
+}
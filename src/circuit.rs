@@ -1,33 +1,451 @@
-use crate::field::FieldElement;
-use crate::hash_functions::HashFunction;
-use crate::r1cs::{Operation, R1CS, Variable};
+use crate::field::{Bn254, FieldElement, FieldParams};
+use crate::hash_functions::{HashError, HashFunction, PoseidonHash};
+use crate::proof::{
+    COMMITTED_PROOF_MAGIC, PROOF_MAGIC, PROOF_VERSION, ProofError, ProofFormat, ProofHeader,
+    Rejection, Verification,
+};
+use crate::r1cs::{self, ConstraintTemplate, Operation, R1CS, R1csStats, Variable};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::sync::LazyLock;
 
+pub mod builder;
+
+/// Mistakes in a circuit or its inputs, caught before any constraint is checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitError {
+    InvalidWire { gate_index: usize, wire: usize }, // A gate refers to a wire that was never added
+    MissingHashFunction { gate_index: usize }, // A Hash gate in a circuit built without a hash function
+    Hash { gate_index: usize, error: HashError }, // The hash function rejected a Hash gate's inputs
+    UnassignedWire(usize),                     // An allocated wire that no gate outputs to
+    ReadBeforeAssign(usize), // A gate reads an allocated wire before the gate that outputs to it
+}
+
+impl fmt::Display for CircuitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::InvalidWire { gate_index, wire } => {
+                write!(
+                    f,
+                    "gate #{} references wire {}, which does not exist",
+                    gate_index, wire
+                )
+            }
+            CircuitError::MissingHashFunction { gate_index } => write!(
+                f,
+                "gate #{} is a Hash gate but the circuit has no hash function",
+                gate_index
+            ),
+            CircuitError::Hash { gate_index, error } => {
+                write!(f, "hash gate #{} failed: {}", gate_index, error)
+            }
+            CircuitError::UnassignedWire(wire) => {
+                write!(f, "wire {} was allocated but no gate outputs to it", wire)
+            }
+            CircuitError::ReadBeforeAssign(wire) => {
+                write!(f, "wire {} is read before any gate assigns it", wire)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CircuitError {}
+
+/// A proof body as read by `Circuit::decode_proof`
+enum DecodedProof {
+    Witness(R1CS), // v2 and plain v3: the witness (stored constraints are ignored)
+    Committed(CommittedWitness), // Committed v3: public values and commitments
+}
+
+/// Body of a committed proof: public wires in the clear, every other wire only as a commitment
+#[derive(Serialize, Deserialize)]
+struct CommittedWitness {
+    public_values: Vec<FieldElement>, // In `add_public_input` order
+    commitments: Vec<FieldElement>, // `Poseidon(value, blinding)` per `Circuit::private_wires` entry
+}
+
+/// Errors raised while exporting a circuit to circom's `.r1cs` format
+#[derive(Debug)]
+pub enum ExportError {
+    Io(std::io::Error),
+    Circuit(CircuitError),
+    UnsupportedConstraint { index: usize, label: Option<String> }, // Hash constraints have no R1CS form here
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "r1cs file I/O failed: {}", e),
+            ExportError::Circuit(e) => write!(f, "invalid circuit: {}", e),
+            ExportError::UnsupportedConstraint { index, label } => {
+                write!(f, "constraint #{} ", index)?;
+                if let Some(label) = label {
+                    write!(f, "[{}] ", label)?;
+                }
+                write!(f, "is a hash constraint and cannot be exported")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExportError::Io(e) => Some(e),
+            ExportError::Circuit(e) => Some(e),
+            ExportError::UnsupportedConstraint { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<CircuitError> for ExportError {
+    fn from(e: CircuitError) -> Self {
+        ExportError::Circuit(e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Gate {
-    Add(usize, usize, usize),  //Add: input1, input2, output
-    Mul(usize, usize, usize),  //Mul: input1, input2, output
-    Hash(usize, usize, usize), //Hash: input, output
+    Add(usize, usize, usize),                 //Add: input1, input2, output
+    Sub(usize, usize, usize),                 //Sub: input1, input2, output (input1 - input2)
+    Mul(usize, usize, usize),                 //Mul: input1, input2, output
+    Square(usize, usize),                     //Square: input, output (input * input)
+    And(usize, usize, usize),                 //And: input1, input2, output (boolean inputs)
+    Xor(usize, usize, usize),                 //Xor: input1, input2, output (boolean inputs)
+    Or(usize, usize, usize),                  //Or: input1, input2, output (boolean inputs)
+    Hash(usize, usize, usize),                //Hash: input, output
+    Hash3(usize, usize, usize, usize), //Hash3: input1, input2, input3, output (one 3-input hash)
+    AssertEqualConstant(usize, FieldElement), //AssertEqualConstant: wire, public constant it must equal
+    AssertEqual(usize, usize),                //AssertEqual: wire1, wire2 (must hold the same value)
+    Boolean(usize),                           //Boolean: wire (must be 0 or 1)
+    ConditionalSwap(usize, usize, usize, usize, usize), //ConditionalSwap: a, b, selector, out_low, out_high
+    //LinearCombination: Σ coeff * wire = output, in a single constraint
+    LinearCombination {
+        terms: Vec<(usize, FieldElement)>, // (wire, coefficient)
+        output: usize,
+    },
+    //Mux: output = if_one when selector is 1, if_zero when it is 0 (selector must be boolean)
+    Mux {
+        selector: usize,
+        if_zero: usize,
+        if_one: usize,
+        output: usize,
+    },
+    //ToBits: little-endian bit decomposition, each bit boolean and Σ 2^i * bit_i = input
+    ToBits {
+        input: usize,
+        bits: Vec<usize>,
+    },
+    //IsEqual: output = 1 iff a == b, via an inverse hint; not_equal = 1 - output
+    IsEqual {
+        a: usize,
+        b: usize,
+        inverse: usize,
+        not_equal: usize,
+        output: usize,
+    },
+    //Constraint: a raw R1CS row over weighted sums of wires; only checks, assigns no wire
+    Constraint {
+        left: Vec<(usize, FieldElement)>, // (wire, coefficient)
+        right: Vec<(usize, FieldElement)>,
+        output: Vec<(usize, FieldElement)>,
+        operation: Operation, // Any operation but `Hash3`, whose terms are not wires
+    },
+    //NonZero: wire * inverse = 1, with inverse = wire^-1 as a hint (unsatisfiable when wire is 0)
+    NonZero {
+        wire: usize,
+        inverse: usize,
+    },
+    Hash1(usize, usize), //Hash1: input, output (one-input hash, Poseidon with t = 2)
+}
+
+impl Gate {
+    /// Every wire the gate reads or writes
+    pub fn wires(&self) -> Vec<usize> {
+        match self {
+            Gate::Add(a, b, out)
+            | Gate::Sub(a, b, out)
+            | Gate::Mul(a, b, out)
+            | Gate::And(a, b, out)
+            | Gate::Xor(a, b, out)
+            | Gate::Or(a, b, out)
+            | Gate::Hash(a, b, out) => vec![*a, *b, *out],
+            Gate::Hash3(a, b, c, out) => vec![*a, *b, *c, *out],
+            Gate::Hash1(input, out) => vec![*input, *out],
+            Gate::Square(input, out) | Gate::AssertEqual(input, out) => vec![*input, *out],
+            Gate::AssertEqualConstant(wire, _) | Gate::Boolean(wire) => vec![*wire],
+            Gate::ConditionalSwap(a, b, selector, out_low, out_high) => {
+                vec![*a, *b, *selector, *out_low, *out_high]
+            }
+            Gate::LinearCombination { terms, output } => terms
+                .iter()
+                .map(|(wire, _)| *wire)
+                .chain([*output])
+                .collect(),
+            Gate::Mux {
+                selector,
+                if_zero,
+                if_one,
+                output,
+            } => vec![*selector, *if_zero, *if_one, *output],
+            Gate::ToBits { input, bits } => {
+                [*input].into_iter().chain(bits.iter().copied()).collect()
+            }
+            Gate::IsEqual {
+                a,
+                b,
+                inverse,
+                not_equal,
+                output,
+            } => vec![*a, *b, *inverse, *not_equal, *output],
+            Gate::Constraint {
+                left,
+                right,
+                output,
+                ..
+            } => left
+                .iter()
+                .chain(right)
+                .chain(output)
+                .map(|(wire, _)| *wire)
+                .collect(),
+            Gate::NonZero { wire, inverse } => vec![*wire, *inverse],
+        }
+    }
+
+    /// Mutable view of the same wires as `wires`, in the same order
+    fn wires_mut(&mut self) -> Vec<&mut usize> {
+        match self {
+            Gate::Add(a, b, out)
+            | Gate::Sub(a, b, out)
+            | Gate::Mul(a, b, out)
+            | Gate::And(a, b, out)
+            | Gate::Xor(a, b, out)
+            | Gate::Or(a, b, out)
+            | Gate::Hash(a, b, out) => vec![a, b, out],
+            Gate::Hash3(a, b, c, out) => vec![a, b, c, out],
+            Gate::Hash1(input, out) => vec![input, out],
+            Gate::Square(input, out) | Gate::AssertEqual(input, out) => vec![input, out],
+            Gate::AssertEqualConstant(wire, _) | Gate::Boolean(wire) => vec![wire],
+            Gate::ConditionalSwap(a, b, selector, out_low, out_high) => {
+                vec![a, b, selector, out_low, out_high]
+            }
+            Gate::LinearCombination { terms, output } => terms
+                .iter_mut()
+                .map(|(wire, _)| wire)
+                .chain([output])
+                .collect(),
+            Gate::Mux {
+                selector,
+                if_zero,
+                if_one,
+                output,
+            } => vec![selector, if_zero, if_one, output],
+            Gate::ToBits { input, bits } => [input].into_iter().chain(bits.iter_mut()).collect(),
+            Gate::IsEqual {
+                a,
+                b,
+                inverse,
+                not_equal,
+                output,
+            } => vec![a, b, inverse, not_equal, output],
+            Gate::Constraint {
+                left,
+                right,
+                output,
+                ..
+            } => left
+                .iter_mut()
+                .chain(right.iter_mut())
+                .chain(output.iter_mut())
+                .map(|(wire, _)| wire)
+                .collect(),
+            Gate::NonZero { wire, inverse } => vec![wire, inverse],
+        }
+    }
+}
+
+impl fmt::Display for Gate {
+    /// Compact form with inputs in parentheses and outputs after the arrow, e.g. `Add(0,1)->2`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Gate::Add(a, b, out) => write!(f, "Add({},{})->{}", a, b, out),
+            Gate::Sub(a, b, out) => write!(f, "Sub({},{})->{}", a, b, out),
+            Gate::Mul(a, b, out) => write!(f, "Mul({},{})->{}", a, b, out),
+            Gate::And(a, b, out) => write!(f, "And({},{})->{}", a, b, out),
+            Gate::Xor(a, b, out) => write!(f, "Xor({},{})->{}", a, b, out),
+            Gate::Or(a, b, out) => write!(f, "Or({},{})->{}", a, b, out),
+            Gate::Hash(a, b, out) => write!(f, "Hash({},{})->{}", a, b, out),
+            Gate::Hash3(a, b, c, out) => write!(f, "Hash3({},{},{})->{}", a, b, c, out),
+            Gate::Hash1(input, out) => write!(f, "Hash1({})->{}", input, out),
+            Gate::Square(input, out) => write!(f, "Square({})->{}", input, out),
+            Gate::AssertEqualConstant(wire, constant) => {
+                write!(f, "AssertEqualConstant({})=={}", wire, constant)
+            }
+            Gate::AssertEqual(a, b) => write!(f, "AssertEqual({},{})", a, b),
+            Gate::Boolean(wire) => write!(f, "Boolean({})", wire),
+            Gate::ConditionalSwap(a, b, selector, out_low, out_high) => write!(
+                f,
+                "ConditionalSwap({},{},{})->({},{})",
+                a, b, selector, out_low, out_high
+            ),
+            Gate::LinearCombination { terms, output } => {
+                write!(f, "LinearCombination({})->{}", format_terms(terms), output)
+            }
+            Gate::Mux {
+                selector,
+                if_zero,
+                if_one,
+                output,
+            } => write!(f, "Mux({},{},{})->{}", selector, if_zero, if_one, output),
+            Gate::ToBits { input, bits } => {
+                let bits: Vec<String> = bits.iter().map(usize::to_string).collect();
+                write!(f, "ToBits({})->[{}]", input, bits.join(","))
+            }
+            Gate::IsEqual {
+                a,
+                b,
+                inverse,
+                not_equal,
+                output,
+            } => write!(
+                f,
+                "IsEqual({},{})->{} (hints {},{})",
+                a, b, output, inverse, not_equal
+            ),
+            Gate::Constraint {
+                left,
+                right,
+                output,
+                operation,
+            } => {
+                let operation = match operation {
+                    Operation::Add => "+".to_string(),
+                    Operation::Sub => "-".to_string(),
+                    Operation::Mul => "*".to_string(),
+                    Operation::Hash => "hash".to_string(),
+                    Operation::Hash3(_) => "hash3".to_string(),
+                    Operation::Hash1 => "hash1".to_string(),
+                    Operation::EqualConstant(constant) => format!("=={}", constant),
+                };
+                write!(
+                    f,
+                    "Constraint(({}) {} ({}))->({})",
+                    format_terms(left),
+                    operation,
+                    format_terms(right),
+                    format_terms(output)
+                )
+            }
+            Gate::NonZero { wire, inverse } => write!(f, "NonZero({}) (hint {})", wire, inverse),
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct Circuit {
     hash_function: Option<Box<dyn HashFunction>>, // Uses `Box<dyn ...>` to enable **Runtime Polymorphism**.
-    inputs: Vec<FieldElement>,
+    wires: Vec<Option<FieldElement>>, // `None` marks a slot reserved by `alloc_wire`, filled in during evaluation
+    packed_booleans: PackedBooleans, // Values of `add_boolean_packed` inputs, whose `wires` slot stays `None`
+    packed_outputs: WireSet,         // Gadget bits from `alloc_packed_bit`, evaluated into a bitset
+    public_inputs: Vec<usize>, // Wires known to the verifier; everything else is private witness
     gates: Vec<Gate>,
-    outputs: Vec<FieldElement>,
+    outputs: Vec<usize>, // Wires whose values `verify_and_get_outputs` hands back to the verifier
+    wire_labels: HashMap<usize, String>, // Optional names, only used to make constraint failures readable
+    gate_labels: HashMap<usize, String>,
+    witness: RefCell<Option<Vec<FieldElement>>>, // Every wire's value from the last evaluation, for `get_wire`
 }
 
 impl Circuit {
+    /// Wire 0 always holds the constant 1; use it wherever a gate needs a constant term
+    pub const ONE: usize = r1cs::ONE;
+
+    /// Creates an empty circuit; the constant one takes wire 0, so the first input gets wire 1
     pub fn new(hash_function: Option<Box<dyn HashFunction>>) -> Self {
         Self {
             hash_function,
-            inputs: Vec::new(),
+            wires: vec![Some(FieldElement::one())],
+            packed_booleans: PackedBooleans::default(),
+            packed_outputs: WireSet::default(),
+            public_inputs: Vec::new(),
             gates: Vec::new(),
             outputs: Vec::new(),
+            wire_labels: HashMap::from([(Self::ONE, "one".to_string())]),
+            gate_labels: HashMap::new(),
+            witness: RefCell::new(None),
         }
     }
 
+    /// Adds a private witness value
     pub fn add_input(&mut self, input: FieldElement) -> usize {
-        let index = self.inputs.len();
-        self.inputs.push(input);
+        let index = self.wires.len();
+        self.wires.push(Some(input));
+        index
+    }
+
+    /// Adds a private witness value from anything convertible to a field element,
+    /// e.g. `circuit.add_input_from(1001u64)`
+    pub fn add_input_from<T: Into<FieldElement>>(&mut self, value: T) -> usize {
+        self.add_input(value.into())
+    }
+
+    /// Adds a private witness value with a name that shows up in constraint failure messages
+    pub fn add_input_named(&mut self, input: FieldElement, name: &str) -> usize {
+        let index = self.add_input(input);
+        self.wire_labels.insert(index, name.to_string());
+        index
+    }
+
+    /// Adds a private boolean input kept as a single bit instead of a `FieldElement`, and constrains
+    /// it with `add_boolean`. It stays a bit while the gates are evaluated (see `WitnessValues`) and
+    /// only becomes a field element once the finished witness is bound to the constraints.
+    pub fn add_boolean_packed(&mut self, value: bool) -> usize {
+        let index = self.wires.len();
+        self.wires.push(None);
+        self.packed_booleans.insert(index, value);
+        self.add_boolean(index);
+        index
+    }
+
+    /// Adds a value the verifier also knows. `verify_proof` must be given the same values, in the
+    /// order they were added here.
+    pub fn add_public_input(&mut self, input: FieldElement) -> usize {
+        let index = self.add_input(input);
+        self.public_inputs.push(index);
+        index
+    }
+
+    /// Reserves a fresh wire with no value yet.
+    /// Use it as a gate output and `generate_proof` will compute the value from the gate's inputs.
+    pub fn alloc_wire(&mut self) -> usize {
+        let index = self.wires.len();
+        self.wires.push(None);
+        index
+    }
+
+    /// Same as `alloc_wire`, for a gate output the calling gadget constrains to be boolean. Its value
+    /// is kept as a bit while the gates are evaluated, like an `add_boolean_packed` input.
+    fn alloc_packed_bit(&mut self) -> usize {
+        let index = self.alloc_wire();
+        self.packed_outputs.insert(index);
+        index
+    }
+
+    /// Same as `alloc_wire`, but the wire is named in constraint failure messages
+    pub fn alloc_wire_named(&mut self, name: &str) -> usize {
+        let index = self.alloc_wire();
+        self.wire_labels.insert(index, name.to_string());
         index
     }
 
@@ -35,99 +453,3089 @@ impl Circuit {
         self.gates.push(gate);
     }
 
-    pub fn add_output(&mut self, output: FieldElement) {
-        self.outputs.push(output);
+    /// Adds a gate whose constraints carry `label`, so a failure points straight at it
+    pub fn add_gate_named(&mut self, gate: Gate, label: &str) {
+        self.gate_labels.insert(self.gates.len(), label.to_string());
+        self.add_gate(gate);
+    }
+
+    /// Declares `wire` a result of the circuit. After a successful check, `verify_and_get_outputs`
+    /// returns the proven values of the declared wires in the order they were added here.
+    pub fn add_output(&mut self, wire: usize) {
+        self.outputs.push(wire);
+    }
+
+    /// Wires declared with `add_output`, in declaration order
+    pub fn outputs(&self) -> &[usize] {
+        &self.outputs
     }
 
+    /// Hashes with the circuit's hash function. Panics if there is none or it rejects the inputs;
+    /// `evaluate` reports both as a `CircuitError` instead.
     pub fn apply_hash(&self, a: &FieldElement, b: &FieldElement) -> FieldElement {
+        self.apply_hash_many(&[a.clone(), b.clone()])
+    }
+
+    /// Any-arity form of `apply_hash`, as used to check `Hash1`, `Hash` and `Hash3` constraints
+    pub fn apply_hash_many(&self, inputs: &[FieldElement]) -> FieldElement {
         self.hash_function
             .as_ref()
             .expect("Hash gate used but no hash function provided")
-            .hash(a, b)
+            .hash_many(inputs)
+    }
+
+    /// The gates in insertion order; print them with `{}` for a readable listing
+    pub fn gates(&self) -> &[Gate] {
+        &self.gates
+    }
+
+    pub fn num_gates(&self) -> usize {
+        self.gates.len()
+    }
+
+    /// Number of wires, counting the constant one, inputs and allocated wires alike
+    pub fn num_wires(&self) -> usize {
+        self.wires.len()
+    }
+
+    /// Proves `wire` lies in `[0, 2^n_bits)` by decomposing it into `n_bits` fresh boolean wires.
+    /// A value that does not fit makes the recomposition constraint fail. `n_bits` must stay below
+    /// the field size (254 bits), otherwise the sum wraps around the modulus and proves nothing.
+    pub fn add_range_check(&mut self, wire: usize, n_bits: usize) {
+        assert!(
+            n_bits < 254,
+            "range check of {} bits wraps around the field",
+            n_bits
+        );
+        let bits = (0..n_bits).map(|_| self.alloc_packed_bit()).collect();
+        self.add_gate(Gate::ToBits { input: wire, bits });
+    }
+
+    /// Returns a boolean wire that is 1 iff `a < b`.
+    ///
+    /// Both inputs must already fit in `n_bits` (range-check them first if they are untrusted): the
+    /// gadget decomposes `a - b + 2^n_bits` into `n_bits + 1` bits, and its top bit is set exactly
+    /// when `a >= b`. Out-of-range inputs can make the result meaningless.
+    pub fn add_less_than(&mut self, a: usize, b: usize, n_bits: usize) -> usize {
+        assert!(
+            n_bits < 253,
+            "comparison of {} bits wraps around the field",
+            n_bits
+        );
+        let one = Self::ONE;
+
+        let shifted = self.alloc_wire();
+        self.add_gate(Gate::LinearCombination {
+            terms: vec![
+                (a, FieldElement::one()),
+                (b, -FieldElement::one()),
+                (one, FieldElement::from_i32(2).pow_u64(n_bits as u64)),
+            ],
+            output: shifted,
+        });
+        let bits: Vec<usize> = (0..=n_bits).map(|_| self.alloc_packed_bit()).collect();
+        let top_bit = bits[n_bits];
+        self.add_gate(Gate::ToBits {
+            input: shifted,
+            bits,
+        });
+
+        let less_than = self.alloc_wire();
+        self.add_gate(Gate::LinearCombination {
+            terms: vec![(one, FieldElement::one()), (top_bit, -FieldElement::one())],
+            output: less_than,
+        });
+        less_than
+    }
+
+    /// Adds one constraint over signed weighted sums of wires, e.g. `a - 2b = c` as
+    /// `add_weighted_constraint(&[(a, 1), (b, -2)], &[], &[(c, 1)], Operation::Add)`.
+    /// Negative weights become `P - |w|`. The gate assigns no wire, so every wire it uses needs a
+    /// value from an input or another gate. `Hash3` is not accepted: use `Gate::Hash3`.
+    pub fn add_weighted_constraint(
+        &mut self,
+        left: &[(usize, i64)],
+        right: &[(usize, i64)],
+        output: &[(usize, i64)],
+        op: Operation,
+    ) {
+        assert!(
+            !matches!(op, Operation::Hash3(_)),
+            "Hash3 carries its own terms; use Gate::Hash3"
+        );
+        let weighted = |terms: &[(usize, i64)]| {
+            terms
+                .iter()
+                .map(|&(wire, weight)| (wire, FieldElement::from_i64(weight)))
+                .collect()
+        };
+        self.add_gate(Gate::Constraint {
+            left: weighted(left),
+            right: weighted(right),
+            output: weighted(output),
+            operation: op,
+        });
+    }
+
+    /// Constrains `wire` to be 0 or 1 with `b * (b - 1) = 0`
+    pub fn add_boolean(&mut self, wire: usize) {
+        self.add_gate(Gate::Boolean(wire));
+    }
+
+    /// Constrains `wire` to be nonzero, e.g. before it is used as a divisor.
+    /// The prover supplies `inv = wire^-1` as a hint and `wire * inv = 1` is enforced.
+    pub fn add_nonzero(&mut self, wire: usize) {
+        let inverse = self.alloc_wire();
+        self.add_gate(Gate::NonZero { wire, inverse });
+    }
+
+    /// Requires `a == b` only when `flag` is 1: `flag` is constrained boolean and
+    /// `flag * (a - b) = 0` is enforced, which any `a`, `b` satisfy when `flag` is 0.
+    pub fn add_conditional_equal(&mut self, flag: usize, a: usize, b: usize) {
+        self.add_boolean(flag);
+        self.add_weighted_constraint(&[(flag, 1)], &[(a, 1), (b, -1)], &[], Operation::Mul);
+    }
+
+    /// Returns a boolean wire that is 1 iff `a == b`.
+    ///
+    /// The prover supplies `inv = (a - b)^-1` (or 0 when equal) as a hint; `(a - b) * inv = 1 - eq`
+    /// and `(a - b) * eq = 0` then leave no freedom in `eq`.
+    pub fn add_is_equal(&mut self, a: usize, b: usize) -> usize {
+        let inverse = self.alloc_wire();
+        let not_equal = self.alloc_wire();
+        let output = self.alloc_wire();
+        self.add_gate(Gate::IsEqual {
+            a,
+            b,
+            inverse,
+            not_equal,
+            output,
+        });
+        output
+    }
+
+    /// Returns a wire holding `base^exp` for a constant `exp`, by square-and-multiply from the
+    /// top bit down: one `Square` per remaining bit plus one `Mul` per set bit. No gates are
+    /// added for `exp = 1` (returns `base`) or `exp = 0` (returns `Self::ONE`).
+    pub fn add_pow_fixed(&mut self, base: usize, exp: u32) -> usize {
+        if exp == 0 {
+            return Self::ONE;
+        }
+
+        let mut result = base;
+        for bit in (0..exp.ilog2()).rev() {
+            let squared = self.alloc_wire();
+            self.add_gate(Gate::Square(result, squared));
+            result = squared;
+            if exp & (1 << bit) != 0 {
+                let product = self.alloc_wire();
+                self.add_gate(Gate::Mul(result, base, product));
+                result = product;
+            }
+        }
+        result
+    }
+
+    /// Proves knowledge of `preimage` with `hash(preimage, salt) = commitment`. Keep `preimage`
+    /// (and usually `salt`) private and make `commitment` a public input.
+    pub fn add_preimage_check(&mut self, preimage: usize, salt: usize, commitment: usize) {
+        let digest = self.alloc_wire();
+        self.add_gate(Gate::Hash(preimage, salt, digest));
+        self.add_gate(Gate::AssertEqual(digest, commitment));
+    }
+
+    /// Constrains `b` to be a permutation of `a` (the same values with the same multiplicities).
+    ///
+    /// Fiat–Shamir as in `Transcript`, but inside the circuit: every wire of `a` then `b` is
+    /// absorbed with a chain of `Hash` gates, and the final state is the challenge `r`. The gadget
+    /// then asserts `Π (a_i - r) = Π (b_i - r)`; two different multisets give the same product for
+    /// at most `n` of the ~2^254 possible `r`. Deriving `r` outside the circuit would leave it
+    /// unconstrained, so this needs the circuit's hash function.
+    pub fn add_permutation_check(&mut self, a: &[usize], b: &[usize]) {
+        assert_eq!(
+            a.len(),
+            b.len(),
+            "a permutation needs sequences of equal length ({} vs {})",
+            a.len(),
+            b.len()
+        );
+        if a.is_empty() {
+            return;
+        }
+
+        let mut absorbed = a.iter().chain(b);
+        let mut challenge = *absorbed.next().expect("a is not empty");
+        for &wire in absorbed {
+            let state = self.alloc_wire();
+            self.add_gate(Gate::Hash(challenge, wire, state));
+            challenge = state;
+        }
+
+        let product_a = self.add_shifted_product(a, challenge);
+        let product_b = self.add_shifted_product(b, challenge);
+        self.add_gate(Gate::AssertEqual(product_a, product_b));
+    }
+
+    /// Returns a wire holding `Π (wire - shift)` over `wires`, which must not be empty
+    fn add_shifted_product(&mut self, wires: &[usize], shift: usize) -> usize {
+        let mut product = None;
+        for &wire in wires {
+            let difference = self.alloc_wire();
+            self.add_gate(Gate::Sub(wire, shift, difference));
+            product = Some(match product {
+                None => difference,
+                Some(product) => {
+                    let next = self.alloc_wire();
+                    self.add_gate(Gate::Mul(product, difference, next));
+                    next
+                }
+            });
+        }
+        product.expect("wires is not empty")
+    }
+
+    /// Proves `leaf` sits in a Merkle tree with root `expected_root`, for any leaf position.
+    ///
+    /// `path` holds the sibling wires from the leaf level up (as from `MerkleTree::get_proof`) and
+    /// `index_bits` the leaf index bits, least significant first (`1` = current node is the right
+    /// child). Each level orders the pair with a `ConditionalSwap` and hashes it.
+    pub fn add_merkle_path_verify(
+        &mut self,
+        leaf: usize,
+        path: &[usize],
+        index_bits: &[usize],
+        expected_root: usize,
+    ) {
+        assert_eq!(
+            path.len(),
+            index_bits.len(),
+            "Merkle path needs one index bit per level"
+        );
+
+        let mut current = leaf;
+        for (&sibling, &bit) in path.iter().zip(index_bits) {
+            let left = self.alloc_wire();
+            let right = self.alloc_wire();
+            self.add_gate(Gate::ConditionalSwap(current, sibling, bit, left, right));
+            let parent = self.alloc_wire();
+            self.add_gate(Gate::Hash(left, right, parent));
+            current = parent;
+        }
+        self.add_gate(Gate::AssertEqual(current, expected_root));
     }
 
-    /// Retrieves an input value by index, if it exists
+    /// Proves membership of a note in a Merkle tree of commitments and returns its public nullifier.
+    ///
+    /// The commitment `hash(secret, blinding)` must sit under `root` at the position given by
+    /// `path` and `index_bits` (see `add_merkle_path_verify`). The nullifier is the one-input hash
+    /// of the secret (`Gate::Hash1`, Poseidon with t = 2), so it never collides with a two-input
+    /// commitment, whatever the blinding. It depends on the secret alone: spending the same note
+    /// twice reveals the same nullifier without revealing which leaf it came from.
+    ///
+    /// The returned nullifier wire is made public: pass its value to `verify_proof` at this point
+    /// in the public input order (after every `add_public_input` made before this call).
+    pub fn add_nullifier_membership(
+        &mut self,
+        secret: usize,
+        blinding: usize,
+        path: &[usize],
+        index_bits: &[usize],
+        root: usize,
+    ) -> usize {
+        let commitment = self.alloc_wire();
+        self.add_gate(Gate::Hash(secret, blinding, commitment));
+        self.add_merkle_path_verify(commitment, path, index_bits, root);
+
+        let nullifier = self.alloc_wire();
+        self.add_gate(Gate::Hash1(secret, nullifier));
+        self.public_inputs.push(nullifier);
+        nullifier
+    }
+
+    /// Copies `other`'s gates into this circuit, so a gadget can be built once and reused.
+    ///
+    /// `other`'s inputs (every wire created with `add_input` or `add_public_input`, in creation order)
+    /// are connected to the parent wires in `input_mapping`; their values in `other` are ignored.
+    /// `other`'s constant one is this circuit's constant one.
+    /// Each allocated wire of `other` gets a fresh wire here, and those are returned in allocation
+    /// order as the sub-circuit's outputs. Hash gates use this circuit's hash function.
+    pub fn embed(&mut self, other: &Circuit, input_mapping: &[usize]) -> Vec<usize> {
+        let n_inputs = (Self::ONE + 1..other.wires.len())
+            .filter(|index| other.is_input(*index))
+            .count();
+        assert_eq!(
+            n_inputs,
+            input_mapping.len(),
+            "sub-circuit has {} inputs but {} parent wires were given",
+            n_inputs,
+            input_mapping.len()
+        );
+
+        let mut parent_inputs = input_mapping.iter();
+        let mut outputs = Vec::new();
+        let mut wire_map = Vec::with_capacity(other.wires.len());
+        for index in 0..other.wires.len() {
+            if index == Self::ONE {
+                wire_map.push(Self::ONE);
+            } else if other.is_input(index) {
+                wire_map.push(*parent_inputs.next().expect("input count checked above"));
+            } else {
+                let parent = if other.packed_outputs.contains(index) {
+                    self.alloc_packed_bit()
+                } else {
+                    self.alloc_wire()
+                };
+                if let Some(label) = other.wire_labels.get(&index) {
+                    self.wire_labels.insert(parent, label.clone());
+                }
+                outputs.push(parent);
+                wire_map.push(parent);
+            }
+        }
+
+        for (gate_index, gate) in other.gates.iter().enumerate() {
+            let mut gate = gate.clone();
+            for wire in gate.wires_mut() {
+                *wire = wire_map[*wire];
+            }
+            match other.gate_labels.get(&gate_index) {
+                Some(label) => self.add_gate_named(gate, label),
+                None => self.add_gate(gate),
+            }
+        }
+        outputs
+    }
+
+    /// Whether both circuits have the same gates, public wires and wire values (inputs, plus which
+    /// wires are still unassigned). Labels, the hash function and any computed witness are ignored.
+    pub fn structurally_eq(&self, other: &Circuit) -> bool {
+        self.gates == other.gates
+            && self.public_inputs == other.public_inputs
+            && self.wires == other.wires
+            && self.packed_booleans == other.packed_booleans
+    }
+
+    /// SHA-256 of the circuit's structure: wire count, public wires and gates (with their constants).
+    /// Input values, labels and the hash function are not included.
+    pub fn digest(&self) -> [u8; 32] {
+        let structure = (self.wires.len(), &self.public_inputs, &self.gates);
+        let encoded = bincode::serialize(&structure).expect("gates are always serializable");
+        Sha256::digest(encoded).into()
+    }
+
+    /// Checks that every gate only references wires that exist, and that Hash gates have a hash function
+    pub fn validate(&self) -> Result<(), CircuitError> {
+        for (gate_index, gate) in self.gates.iter().enumerate() {
+            if let Some(wire) = gate.wires().into_iter().find(|w| *w >= self.wires.len()) {
+                return Err(CircuitError::InvalidWire { gate_index, wire });
+            }
+            let hashes = matches!(
+                gate,
+                Gate::Hash(..)
+                    | Gate::Hash3(..)
+                    | Gate::Hash1(..)
+                    | Gate::Constraint {
+                        operation: Operation::Hash | Operation::Hash1,
+                        ..
+                    }
+            );
+            if hashes && self.hash_function.is_none() {
+                return Err(CircuitError::MissingHashFunction { gate_index });
+            }
+        }
+        Ok(())
+    }
+
+    /// Size of the constraint system this circuit compiles to, without writing a proof.
+    /// Gates referencing missing wires are an error, as in `evaluate`.
+    pub fn stats(&self) -> Result<R1csStats, CircuitError> {
+        self.validate()?;
+        Ok(self.synthesize().stats())
+    }
+
+    /// Bytes `generate_proof` would write, added up from the encoded size of every value, label and
+    /// length prefix without serializing anything.
+    ///
+    /// The witness is computed so each value is sized exactly, and the result then matches the file.
+    /// If that fails (a hash the hash function rejects, say; `generate_proof` would fail too),
+    /// unknown gate outputs are counted at full width and the result is an upper bound.
+    /// Gates referencing missing wires are an error, as in `evaluate`.
+    pub fn estimated_proof_size(&self) -> Result<usize, CircuitError> {
+        self.validate()?;
+        let template = self.synthesize();
+        let witness = self.compute_witness().ok();
+        let variable_size = |variable: &Variable| {
+            let value = match &witness {
+                Some(witness) => witness.get(variable.index),
+                None => self.get_input(variable.index),
+            };
+            let value_size = value.map_or(MAX_ENCODED_FIELD_SIZE, encoded_field_size);
+            8 + value_size + encoded_label_size(&variable.label)
+        };
+        let terms_size = |terms: &[(Variable, FieldElement)]| {
+            8 + terms
+                .iter()
+                .map(|(variable, coeff)| variable_size(variable) + encoded_field_size(coeff))
+                .sum::<usize>()
+        };
+
+        let variables = 8 + template.variables.iter().map(variable_size).sum::<usize>();
+        let constraints = 8 + template
+            .constraints
+            .iter()
+            .map(|constraint| {
+                let operation = 4 + match &constraint.operation {
+                    Operation::Hash3(third) => terms_size(third),
+                    Operation::EqualConstant(constant) => encoded_field_size(constant),
+                    Operation::Add
+                    | Operation::Sub
+                    | Operation::Mul
+                    | Operation::Hash
+                    | Operation::Hash1 => 0,
+                };
+                terms_size(&constraint.left)
+                    + terms_size(&constraint.right)
+                    + terms_size(&constraint.output)
+                    + operation
+                    + encoded_label_size(&constraint.label)
+            })
+            .sum::<usize>();
+        let public_inputs = 8 + 8 * template.public_inputs.len();
+
+        Ok(PROOF_HEADER_SIZE + variables + constraints + public_inputs)
+    }
+
+    /// Retrieves an input value by index, if it exists (allocated wires have no value until evaluation)
     pub fn get_input(&self, index: usize) -> Option<&FieldElement> {
-        self.inputs.get(index)
+        match self.packed_booleans.get(index) {
+            Some(bit) => Some(&BIT_VALUES[bit as usize]),
+            None => self.wires.get(index).and_then(Option::as_ref),
+        }
+    }
+
+    /// Whether `index` was created with one of the `add_*input*` methods rather than allocated
+    fn is_input(&self, index: usize) -> bool {
+        self.wires[index].is_some() || self.packed_booleans.get(index).is_some()
+    }
+
+    /// Value of any wire, including gate outputs, as computed by the last `evaluate`, `generate_proof` or
+    /// `export_witness`. Before that only inputs have values.
+    pub fn get_wire(&self, index: usize) -> Option<FieldElement> {
+        match self
+            .witness
+            .borrow()
+            .as_ref()
+            .and_then(|witness| witness.get(index))
+        {
+            Some(value) => Some(value.clone()),
+            None => self.get_input(index).cloned(),
+        }
+    }
+
+    /// Evaluates the gates and returns every wire's value in wire order, caching it for `get_wire`.
+    fn compute_witness(&self) -> Result<Vec<FieldElement>, CircuitError> {
+        let witness = self.evaluate_wires()?.into_witness()?;
+        *self.witness.borrow_mut() = Some(witness.clone());
+        Ok(witness)
+    }
+
+    /// Evaluates the gates in order, filling every allocated output wire from the gate's inputs.
+    /// Wires that already hold a value are left untouched so the constraints can still catch a wrong claim.
+    fn evaluate_wires(&self) -> Result<WitnessValues<'_>, CircuitError> {
+        let mut values = WitnessValues::new(self);
+
+        for (gate_index, gate) in self.gates.iter().enumerate() {
+            match gate {
+                Gate::Add(a, b, output) => {
+                    let sum = wire_value(&values, *a)? + wire_value(&values, *b)?;
+                    fill_wire(&mut values, *output, sum);
+                }
+                Gate::Sub(a, b, output) => {
+                    let difference = wire_value(&values, *a)? - wire_value(&values, *b)?;
+                    fill_wire(&mut values, *output, difference);
+                }
+                Gate::Mul(a, b, output) => {
+                    let product = wire_value(&values, *a)? * wire_value(&values, *b)?;
+                    fill_wire(&mut values, *output, product);
+                }
+                Gate::Square(input, output) => {
+                    let value = wire_value(&values, *input)?;
+                    fill_wire(&mut values, *output, &value * &value);
+                }
+                Gate::Hash(a, b, output) => {
+                    let hash_function = self
+                        .hash_function
+                        .as_ref()
+                        .ok_or(CircuitError::MissingHashFunction { gate_index })?;
+                    let (a_value, b_value) = (wire_value(&values, *a)?, wire_value(&values, *b)?);
+                    let computed_hash = hash_function
+                        .try_hash(&a_value, &b_value)
+                        .map_err(|error| CircuitError::Hash { gate_index, error })?;
+                    println!(
+                        "Applying Hash constraint: input_a = {}, input_b = {}, computed_hash = {}, output_index = {}",
+                        a_value, b_value, computed_hash, output
+                    );
+                    fill_wire(&mut values, *output, computed_hash);
+                }
+                Gate::Hash3(a, b, c, output) => {
+                    let hash_function = self
+                        .hash_function
+                        .as_ref()
+                        .ok_or(CircuitError::MissingHashFunction { gate_index })?;
+                    let inputs = [
+                        wire_value(&values, *a)?,
+                        wire_value(&values, *b)?,
+                        wire_value(&values, *c)?,
+                    ];
+                    let computed_hash = hash_function
+                        .try_hash_many(&inputs)
+                        .map_err(|error| CircuitError::Hash { gate_index, error })?;
+                    fill_wire(&mut values, *output, computed_hash);
+                }
+                Gate::Hash1(input, output) => {
+                    let hash_function = self
+                        .hash_function
+                        .as_ref()
+                        .ok_or(CircuitError::MissingHashFunction { gate_index })?;
+                    let computed_hash = hash_function
+                        .try_hash_many(&[wire_value(&values, *input)?])
+                        .map_err(|error| CircuitError::Hash { gate_index, error })?;
+                    fill_wire(&mut values, *output, computed_hash);
+                }
+                Gate::ConditionalSwap(a, b, selector, out_low, out_high) => {
+                    // low = a + s * (b - a), high = a + b - low.
+                    // With s in {0, 1} that is (a, b) or (b, a); any other s is caught by the boolean constraint.
+                    let a_value = wire_value(&values, *a)?;
+                    let b_value = wire_value(&values, *b)?;
+                    let s_value = wire_value(&values, *selector)?;
+                    let low = &a_value + &(&s_value * &(b_value.clone() - a_value.clone()));
+                    let high = a_value + b_value - low.clone();
+                    fill_wire(&mut values, *out_low, low);
+                    fill_wire(&mut values, *out_high, high);
+                }
+                Gate::LinearCombination { terms, output } => {
+                    let mut sum = FieldElement::zero();
+                    for (wire, coeff) in terms {
+                        sum += &wire_value(&values, *wire)? * coeff;
+                    }
+                    fill_wire(&mut values, *output, sum);
+                }
+                Gate::And(a, b, output) => {
+                    let and = wire_value(&values, *a)? * wire_value(&values, *b)?;
+                    fill_wire(&mut values, *output, and);
+                }
+                Gate::Xor(a, b, output) => {
+                    let (a_value, b_value) = (wire_value(&values, *a)?, wire_value(&values, *b)?);
+                    let product = &a_value * &b_value;
+                    let xor = a_value + b_value - FieldElement::from_i32(2) * product;
+                    fill_wire(&mut values, *output, xor);
+                }
+                Gate::Or(a, b, output) => {
+                    let (a_value, b_value) = (wire_value(&values, *a)?, wire_value(&values, *b)?);
+                    let product = &a_value * &b_value;
+                    let or = a_value + b_value - product;
+                    fill_wire(&mut values, *output, or);
+                }
+                Gate::Mux {
+                    selector,
+                    if_zero,
+                    if_one,
+                    output,
+                } => {
+                    let zero_value = wire_value(&values, *if_zero)?;
+                    let one_value = wire_value(&values, *if_one)?;
+                    let s_value = wire_value(&values, *selector)?;
+                    let selected = &zero_value + &(&s_value * &(one_value - zero_value.clone()));
+                    fill_wire(&mut values, *output, selected);
+                }
+                Gate::ToBits { input, bits } => {
+                    let value = wire_value(&values, *input)?.value;
+                    for (i, bit) in bits.iter().enumerate() {
+                        let bit_value = FieldElement::from_i32(value.bit(i as u64) as i32);
+                        fill_wire(&mut values, *bit, bit_value);
+                    }
+                }
+                Gate::IsEqual {
+                    a,
+                    b,
+                    inverse,
+                    not_equal,
+                    output,
+                } => {
+                    let diff = wire_value(&values, *a)? - wire_value(&values, *b)?;
+                    let diff_inverse = diff.inverse().unwrap_or_else(FieldElement::zero);
+                    let not_equal_value = &diff * &diff_inverse;
+                    fill_wire(&mut values, *inverse, diff_inverse);
+                    fill_wire(
+                        &mut values,
+                        *output,
+                        FieldElement::one() - not_equal_value.clone(),
+                    );
+                    fill_wire(&mut values, *not_equal, not_equal_value);
+                }
+                Gate::NonZero { wire, inverse } => {
+                    // Zero has no inverse; the hint 0 then fails `wire * inverse = 1` as it should
+                    let inverse_value = wire_value(&values, *wire)?
+                        .inverse()
+                        .unwrap_or_else(FieldElement::zero);
+                    fill_wire(&mut values, *inverse, inverse_value);
+                }
+                // Only constrain existing wires, nothing to compute
+                Gate::AssertEqualConstant(..)
+                | Gate::AssertEqual(..)
+                | Gate::Boolean(..)
+                | Gate::Constraint { .. } => {}
+            }
+        }
+
+        Ok(values)
     }
 
-    /// Generates the proof and checks if the constraints are met, in which case it's saved to a binary file
-    pub fn generate_proof(&self, proof_file: &str) {
+    /// Turns the gates into constraints without evaluating anything.
+    /// The template can then be bound to any number of witnesses with `ConstraintTemplate::assign`.
+    pub fn synthesize(&self) -> ConstraintTemplate {
+        // Placeholder variables: they only identify wires until `assign` binds real values
         let mut r1cs = R1CS::new();
-        r1cs.variables = self
-            .inputs
-            .iter()
-            .enumerate()
-            .map(|(index, value)| Variable {
+        r1cs.variables = (0..self.wires.len())
+            .map(|index| Variable {
                 index,
-                value: value.clone(),
+                value: FieldElement::zero(),
+                label: self.wire_labels.get(&index).cloned(),
             })
-            .collect(); //Every input is turned to variables in R1cs
+            .collect(); //Every wire is turned to variables in R1cs
+        r1cs.public_inputs = self.public_inputs.clone();
+
+        for (gate_index, gate) in self.gates.iter().enumerate() {
+            let first_constraint = r1cs.constraints.len();
 
-        for gate in &self.gates {
             match gate {
                 //Addition Gate
                 Gate::Add(a, b, output) => {
                     r1cs.add_constraint(
-                        vec![(r1cs.variables[*a].clone(), FieldElement::from_i32(1))],
-                        vec![(r1cs.variables[*b].clone(), FieldElement::from_i32(1))],
-                        vec![(r1cs.variables[*output].clone(), FieldElement::from_i32(1))],
+                        vec![(r1cs.variables[*a].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*b].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*output].clone(), FieldElement::one())],
                         Operation::Add,
                     );
                 }
 
+                //Subtraction gate
+                Gate::Sub(a, b, output) => {
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*a].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*b].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*output].clone(), FieldElement::one())],
+                        Operation::Sub,
+                    );
+                }
+
                 //Multiplication gate
                 Gate::Mul(a, b, output) => {
                     r1cs.add_constraint(
-                        vec![(r1cs.variables[*a].clone(), FieldElement::from_i32(1))],
-                        vec![(r1cs.variables[*b].clone(), FieldElement::from_i32(1))],
-                        vec![(r1cs.variables[*output].clone(), FieldElement::from_i32(1))],
+                        vec![(r1cs.variables[*a].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*b].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*output].clone(), FieldElement::one())],
+                        Operation::Mul,
+                    );
+                }
+
+                //Squaring gate: a multiplication constraint of the input with itself
+                Gate::Square(input, output) => {
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*input].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*input].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*output].clone(), FieldElement::one())],
                         Operation::Mul,
                     );
                 }
 
                 //Hashing gate
                 Gate::Hash(a, b, output) => {
-                    let computed_hash = self.apply_hash(&self.inputs[*a], &self.inputs[*b]);
-                    r1cs.variables[*output].value = computed_hash.clone();
                     r1cs.add_constraint(
-                        vec![(r1cs.variables[*a].clone(), FieldElement::from_i32(1))],
-                        vec![(r1cs.variables[*b].clone(), FieldElement::from_i32(1))],
-                        vec![(r1cs.variables[*output].clone(), FieldElement::from_i32(1))],
+                        vec![(r1cs.variables[*a].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*b].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*output].clone(), FieldElement::one())],
                         Operation::Hash,
                     );
+                }
 
-                    println!(
-                        "Applying Hash constraint: input_a = {}, input_b = {}, computed_hash = {}, output_index = {}",
-                        self.inputs[*a], self.inputs[*b], computed_hash, output
+                //Three-input hash gate: hash(a, b, c) = output, with c carried by the operation
+                Gate::Hash3(a, b, c, output) => {
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*a].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*b].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*output].clone(), FieldElement::one())],
+                        Operation::Hash3(vec![(r1cs.variables[*c].clone(), FieldElement::one())]),
                     );
                 }
-            }
-        }
 
-        // Validate all constraints
-        let is_valid = r1cs.is_satisfied(|a, b| {
-            self.hash_function
-                .as_ref()
-                .expect("Hash gate used but no hash function provided")
-                .hash(a, b)
-        });
+                //One-input hash gate: hash(input) = output, with nothing on the right
+                Gate::Hash1(input, output) => {
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*input].clone(), FieldElement::one())],
+                        vec![],
+                        vec![(r1cs.variables[*output].clone(), FieldElement::one())],
+                        Operation::Hash1,
+                    );
+                }
+
+                //Conditional swap gate: (a, b) if selector = 0, (b, a) if selector = 1
+                Gate::ConditionalSwap(a, b, selector, out_low, out_high) => {
+                    boolean_constraint(&mut r1cs, *selector);
+                    // selector * (b - a) = out_low - a
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*selector].clone(), FieldElement::one())],
+                        vec![
+                            (r1cs.variables[*b].clone(), FieldElement::one()),
+                            (r1cs.variables[*a].clone(), -FieldElement::one()),
+                        ],
+                        vec![
+                            (r1cs.variables[*out_low].clone(), FieldElement::one()),
+                            (r1cs.variables[*a].clone(), -FieldElement::one()),
+                        ],
+                        Operation::Mul,
+                    );
+                    // a + b = out_low + out_high, so the pair is a permutation of (a, b)
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*a].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*b].clone(), FieldElement::one())],
+                        vec![
+                            (r1cs.variables[*out_low].clone(), FieldElement::one()),
+                            (r1cs.variables[*out_high].clone(), FieldElement::one()),
+                        ],
+                        Operation::Add,
+                    );
+                }
 
-        // Save proof as bytes to binary file
-        let proof_data = bincode::serialize(&is_valid).expect("Failed to serialize proof"); //The result is a Vec<u8> (vector of bytes)
-        std::fs::write(proof_file, proof_data).expect("Failed to write proof file");
+                //Linear combination gate: (Σ coeff * wire) + 0 = output
+                Gate::LinearCombination { terms, output } => {
+                    r1cs.add_constraint(
+                        terms
+                            .iter()
+                            .map(|(wire, coeff)| (r1cs.variables[*wire].clone(), coeff.clone()))
+                            .collect(),
+                        vec![],
+                        vec![(r1cs.variables[*output].clone(), FieldElement::one())],
+                        Operation::Add,
+                    );
+                }
 
-        if is_valid {
-            println!("✓ Proof generated successfully: {}", proof_file);
-        } else {
-            println!("✗ Proof generation failed: constraints not satisfied");
-        }
-    }
+                //Logic gates: both inputs boolean, then and = a * b, xor = a + b - 2ab, or = a + b - ab
+                Gate::And(a, b, output) => {
+                    boolean_constraint(&mut r1cs, *a);
+                    boolean_constraint(&mut r1cs, *b);
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*a].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*b].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*output].clone(), FieldElement::one())],
+                        Operation::Mul,
+                    );
+                }
+                Gate::Xor(a, b, output) => {
+                    boolean_constraint(&mut r1cs, *a);
+                    boolean_constraint(&mut r1cs, *b);
+                    // 2a * b = a + b - xor
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*a].clone(), FieldElement::from_i32(2))],
+                        vec![(r1cs.variables[*b].clone(), FieldElement::one())],
+                        vec![
+                            (r1cs.variables[*a].clone(), FieldElement::one()),
+                            (r1cs.variables[*b].clone(), FieldElement::one()),
+                            (r1cs.variables[*output].clone(), -FieldElement::one()),
+                        ],
+                        Operation::Mul,
+                    );
+                }
+                Gate::Or(a, b, output) => {
+                    boolean_constraint(&mut r1cs, *a);
+                    boolean_constraint(&mut r1cs, *b);
+                    // a * b = a + b - or
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*a].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*b].clone(), FieldElement::one())],
+                        vec![
+                            (r1cs.variables[*a].clone(), FieldElement::one()),
+                            (r1cs.variables[*b].clone(), FieldElement::one()),
+                            (r1cs.variables[*output].clone(), -FieldElement::one()),
+                        ],
+                        Operation::Mul,
+                    );
+                }
+
+                //Mux gate: selector boolean, then selector * (if_one - if_zero) = output - if_zero
+                Gate::Mux {
+                    selector,
+                    if_zero,
+                    if_one,
+                    output,
+                } => {
+                    boolean_constraint(&mut r1cs, *selector);
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*selector].clone(), FieldElement::one())],
+                        vec![
+                            (r1cs.variables[*if_one].clone(), FieldElement::one()),
+                            (r1cs.variables[*if_zero].clone(), -FieldElement::one()),
+                        ],
+                        vec![
+                            (r1cs.variables[*output].clone(), FieldElement::one()),
+                            (r1cs.variables[*if_zero].clone(), -FieldElement::one()),
+                        ],
+                        Operation::Mul,
+                    );
+                }
+
+                //Bit decomposition gate: bit * (bit - 1) = 0 for every bit, then Σ 2^i * bit_i + 0 = input
+                Gate::ToBits { input, bits } => {
+                    for bit in bits {
+                        boolean_constraint(&mut r1cs, *bit);
+                    }
+                    let two = FieldElement::from_i32(2);
+                    r1cs.add_constraint(
+                        bits.iter()
+                            .enumerate()
+                            .map(|(i, bit)| (r1cs.variables[*bit].clone(), two.pow_u64(i as u64)))
+                            .collect(),
+                        vec![],
+                        vec![(r1cs.variables[*input].clone(), FieldElement::one())],
+                        Operation::Add,
+                    );
+                }
+
+                //Equality test gate: (a - b) * inverse = not_equal, (a - b) * output = 0, not_equal + output = 1
+                Gate::IsEqual {
+                    a,
+                    b,
+                    inverse,
+                    not_equal,
+                    output,
+                } => {
+                    let diff = vec![
+                        (r1cs.variables[*a].clone(), FieldElement::one()),
+                        (r1cs.variables[*b].clone(), -FieldElement::one()),
+                    ];
+                    r1cs.add_constraint(
+                        diff.clone(),
+                        vec![(r1cs.variables[*inverse].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*not_equal].clone(), FieldElement::one())],
+                        Operation::Mul,
+                    );
+                    r1cs.add_constraint(
+                        diff,
+                        vec![(r1cs.variables[*output].clone(), FieldElement::one())],
+                        vec![],
+                        Operation::Mul,
+                    );
+                    r1cs.add_constraint(
+                        vec![
+                            (r1cs.variables[*not_equal].clone(), FieldElement::one()),
+                            (r1cs.variables[*output].clone(), FieldElement::one()),
+                        ],
+                        vec![],
+                        vec![],
+                        Operation::EqualConstant(FieldElement::one()),
+                    );
+                }
+
+                //Boolean gate: b * (b - 1) = 0
+                Gate::Boolean(wire) => boolean_constraint(&mut r1cs, *wire),
+
+                //Equality assertion gate: a - b = 0 (empty output side sums to zero)
+                Gate::AssertEqual(a, b) => {
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*a].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*b].clone(), FieldElement::one())],
+                        vec![],
+                        Operation::Sub,
+                    );
+                }
+
+                //Constant assertion gate
+                Gate::AssertEqualConstant(wire, constant) => {
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*wire].clone(), FieldElement::one())],
+                        vec![],
+                        vec![],
+                        Operation::EqualConstant(constant.clone()),
+                    );
+                }
+
+                //Raw constraint gate: emitted as given, with wires turned into variables
+                Gate::Constraint {
+                    left,
+                    right,
+                    output,
+                    operation,
+                } => {
+                    let to_terms = |terms: &[(usize, FieldElement)]| {
+                        terms
+                            .iter()
+                            .map(|(wire, coeff)| (r1cs.variables[*wire].clone(), coeff.clone()))
+                            .collect()
+                    };
+                    let (left, right, output) = (to_terms(left), to_terms(right), to_terms(output));
+                    r1cs.add_constraint(left, right, output, operation.clone());
+                }
+
+                //Non-zero gate: wire * inverse = 1, which no inverse satisfies when wire is 0
+                Gate::NonZero { wire, inverse } => {
+                    r1cs.add_constraint(
+                        vec![(r1cs.variables[*wire].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[*inverse].clone(), FieldElement::one())],
+                        vec![(r1cs.variables[Self::ONE].clone(), FieldElement::one())],
+                        Operation::Mul,
+                    );
+                }
+            }
+
+            // Every constraint this gate emitted inherits its label
+            if let Some(label) = self.gate_labels.get(&gate_index) {
+                for constraint in &mut r1cs.constraints[first_constraint..] {
+                    constraint.label = Some(label.clone());
+                }
+            }
+        }
+
+        ConstraintTemplate::from(r1cs)
+    }
+
+    /// Dry run: evaluates the gates and binds the witness to the constraints, without writing anything.
+    /// The returned system can be inspected or checked with `R1CS::is_satisfied`; an unsatisfiable
+    /// circuit still evaluates. Gates referencing missing wires, failed hashes and allocated wires
+    /// that are read too early or never assigned are reported as `CircuitError`, never a panic.
+    pub fn evaluate(&self) -> Result<R1CS, CircuitError> {
+        self.validate()?;
+        let witness = self.compute_witness()?;
+        Ok(self.synthesize().assign(&witness))
+    }
+
+    /// Generates the proof and checks if the constraints are met, then saves it to a binary file.
+    /// Returns whether the constraints were satisfied; gates referencing missing wires, IO and serialization
+    /// failures are reported as `ProofError`.
+    pub fn generate_proof(&self, proof_file: &str) -> Result<bool, ProofError> {
+        let r1cs = self.evaluate()?;
+
+        // Validate all constraints
+        let is_valid = r1cs.is_satisfied(|inputs| self.apply_hash_many(inputs));
+
+        // Save the whole constraint system (witness + constraints) so the verifier can re-check it,
+        // behind a header identifying the format and the circuit
+        let header = ProofHeader {
+            magic: PROOF_MAGIC,
+            version: PROOF_VERSION,
+            circuit_digest: self.digest(),
+        };
+        let mut proof_data = bincode::serialize(&header)?; //The result is a Vec<u8> (vector of bytes)
+        proof_data.extend(bincode::serialize(&r1cs)?);
+        std::fs::write(proof_file, proof_data)?;
+
+        if is_valid {
+            println!("✓ Proof generated successfully: {}", proof_file);
+        } else {
+            println!("✗ Proof generation failed: constraints not satisfied");
+        }
+        Ok(is_valid)
+    }
+
+    /// Values of the public wires in the order `verify_proof` expects them, which is also their
+    /// order in the proof file and the exported `.r1cs`. A public wire computed by a gate (such as
+    /// the nullifier of `add_nullifier_membership`) needs the circuit evaluated; if that has not
+    /// happened yet it is done here, and its errors are returned as in `evaluate`.
+    pub fn public_inputs(&self) -> Result<Vec<FieldElement>, CircuitError> {
+        if self
+            .public_inputs
+            .iter()
+            .any(|&wire| self.get_wire(wire).is_none())
+        {
+            self.validate()?;
+            self.compute_witness()?;
+        }
+        Ok(self
+            .public_inputs
+            .iter()
+            .map(|&wire| {
+                self.get_wire(wire)
+                    .expect("a computed witness assigns every wire")
+            })
+            .collect())
+    }
+
+    /// Wires a committed proof hides: everything except the constant one and the public inputs
+    pub fn private_wires(&self) -> Vec<usize> {
+        (Self::ONE + 1..self.wires.len())
+            .filter(|index| !self.public_inputs.contains(index))
+            .collect()
+    }
+
+    /// Like `generate_proof`, but private wires are written only as `Poseidon(value, blinding)`, so
+    /// the file does not reveal them. `blindings` holds one fresh random value per entry of
+    /// `private_wires`, in that order; reusing one lets equal values be linked.
+    ///
+    /// This only hides the witness: a verifier can no longer re-check constraints over private wires,
+    /// so `verify_proof` reports such a proof as `Verification::PartiallyVerified` at best.
+    pub fn generate_committed_proof(
+        &self,
+        proof_file: &str,
+        blindings: &[FieldElement],
+    ) -> Result<bool, ProofError> {
+        let private_wires = self.private_wires();
+        assert_eq!(
+            blindings.len(),
+            private_wires.len(),
+            "{} blindings given for {} private wires",
+            blindings.len(),
+            private_wires.len()
+        );
+
+        let r1cs = self.evaluate()?;
+        let is_valid = r1cs.is_satisfied(|inputs| self.apply_hash_many(inputs));
+
+        let hasher = PoseidonHash::new();
+        let commitments = private_wires
+            .iter()
+            .zip(blindings)
+            .map(|(&wire, blinding)| hasher.try_hash(&r1cs.variables[wire].value, blinding))
+            .collect::<Result<Vec<_>, HashError>>()?;
+        let body = CommittedWitness {
+            public_values: self
+                .public_inputs
+                .iter()
+                .map(|&wire| r1cs.variables[wire].value.clone())
+                .collect(),
+            commitments,
+        };
+
+        let header = ProofHeader {
+            magic: COMMITTED_PROOF_MAGIC,
+            version: PROOF_VERSION,
+            circuit_digest: self.digest(),
+        };
+        let mut proof_data = bincode::serialize(&header)?;
+        proof_data.extend(bincode::serialize(&body)?);
+        std::fs::write(proof_file, proof_data)?;
+
+        if is_valid {
+            println!("✓ Committed proof generated successfully: {}", proof_file);
+        } else {
+            println!("✗ Proof generation failed: constraints not satisfied");
+        }
+        Ok(is_valid)
+    }
+
+    /// Verifies the proof by reading the witness from a binary file and checking it against this
+    /// circuit's constraints, rebuilt with `synthesize`. Only the wire values come from the file; the
+    /// constraints stored next to them are ignored, so a forged file cannot drop or weaken any.
+    /// Committed proofs are read too, but only constraints over public wires can be re-checked: one
+    /// that passes is `Verification::PartiallyVerified`, never `Valid`, unless no constraint touches
+    /// a private wire.
+    ///
+    /// `public_inputs` are the values the verifier expects on the circuit's public wires, in the order
+    /// they were declared with `add_public_input`. The proof is rejected if they differ.
+    /// A proof that can be read but does not hold is `Verification::Rejected`, carrying the reason.
+    /// A file with the wrong magic bytes, format version or circuit digest is an error instead.
+    ///
+    /// Of the older layouts (see `ProofFormat`), a v2 file is checked like a current one: it names
+    /// no circuit, but its witness still has to satisfy this circuit's constraints. A v1 file only
+    /// holds the prover's own verdict and is `ProofError::UnverifiableFormat`.
+    pub fn verify_proof(
+        &self,
+        proof_file: &str,
+        public_inputs: &[FieldElement],
+    ) -> Result<Verification, ProofError> {
+        let file = File::open(proof_file)?;
+        self.verify_proof_reader(BufReader::new(file), public_inputs)
+    }
+
+    /// Same as `verify_proof`, reading the proof from any stream (a socket, an in-memory `Cursor`, ...).
+    /// The proof is decoded as it is read, without buffering the whole file first.
+    pub fn verify_proof_reader<R: Read>(
+        &self,
+        reader: R,
+        public_inputs: &[FieldElement],
+    ) -> Result<Verification, ProofError> {
+        let verification = match self.decode_proof(reader)? {
+            DecodedProof::Witness(r1cs) => self
+                .check_witness(&r1cs, public_inputs)
+                .map(|_| Verification::Valid),
+            DecodedProof::Committed(proof) => self.verify_committed(&proof, public_inputs),
+        };
+        Ok(verification.unwrap_or_else(Verification::Rejected))
+    }
+
+    /// Verifies the proof like `verify_proof` and, if it holds, returns the values of the wires
+    /// declared with `add_output`. A proof that does not hold is `ProofError::Rejected`.
+    /// Only proofs carrying the full witness have output values to return; committed proofs
+    /// are `ProofError::NotAProof` here.
+    pub fn verify_and_get_outputs(
+        &self,
+        proof_file: &str,
+        public_inputs: &[FieldElement],
+    ) -> Result<Vec<FieldElement>, ProofError> {
+        let file = File::open(proof_file)?;
+        let DecodedProof::Witness(r1cs) = self.decode_proof(BufReader::new(file))? else {
+            return Err(ProofError::NotAProof);
+        };
+        let witness = self
+            .check_witness(&r1cs, public_inputs)
+            .map_err(ProofError::Rejected)?;
+        Ok(self
+            .outputs
+            .iter()
+            .map(|&wire| witness[wire].clone())
+            .collect())
+    }
+
+    /// Detects the proof's format and decodes its body. A headered proof naming another circuit is
+    /// turned away here, before anything is checked; a matching digest is no evidence of validity.
+    fn decode_proof<R: Read>(&self, reader: R) -> Result<DecodedProof, ProofError> {
+        self.validate()?;
+        let (format, body) = ProofFormat::detect(reader)?;
+        Ok(match format {
+            ProofFormat::V1Bool => return Err(ProofError::UnverifiableFormat),
+            ProofFormat::V2R1cs => DecodedProof::Witness(
+                bincode::deserialize_from(body).map_err(|_| ProofError::NotAProof)?,
+            ),
+            ProofFormat::V3(header) => {
+                if header.circuit_digest != self.digest() {
+                    return Err(ProofError::CircuitMismatch);
+                }
+                if header.magic == COMMITTED_PROOF_MAGIC {
+                    DecodedProof::Committed(bincode::deserialize_from(body)?)
+                } else {
+                    DecodedProof::Witness(bincode::deserialize_from(body)?)
+                }
+            }
+        })
+    }
+
+    /// Checks a proof's witness against this circuit and returns it. Only the variable values are
+    /// taken from the file: the constraints are this circuit's own, rebuilt by `synthesize`, so
+    /// whatever constraints or public layout the file carries cannot change what is checked.
+    fn check_witness(
+        &self,
+        r1cs: &R1CS,
+        public_inputs: &[FieldElement],
+    ) -> Result<Vec<FieldElement>, Rejection> {
+        if r1cs.variables.len() != self.wires.len() {
+            return Err(Rejection::WitnessSize {
+                found: r1cs.variables.len(),
+                expected: self.wires.len(),
+            });
+        }
+        let witness: Vec<FieldElement> = r1cs
+            .variables
+            .iter()
+            .map(|variable| variable.value.clone())
+            .collect();
+
+        // The public wires must hold the values the verifier expects
+        self.check_public_values(public_inputs, |wire| &witness[wire])?;
+
+        self.synthesize()
+            .assign(&witness)
+            .check(|inputs| self.apply_hash_many(inputs))
+            .map_err(Rejection::Constraint)?;
+        Ok(witness)
+    }
+
+    /// Compares the proof's value for each public wire (looked up with `value`) against
+    /// `public_inputs`, which the verifier supplies in `add_public_input` order
+    fn check_public_values<'a>(
+        &self,
+        public_inputs: &[FieldElement],
+        value: impl Fn(usize) -> &'a FieldElement,
+    ) -> Result<(), Rejection> {
+        if public_inputs.len() != self.public_inputs.len() {
+            return Err(Rejection::PublicInputCount {
+                found: public_inputs.len(),
+                expected: self.public_inputs.len(),
+            });
+        }
+        match self
+            .public_inputs
+            .iter()
+            .zip(public_inputs)
+            .find(|&(&wire, expected)| value(wire) != expected)
+        {
+            Some((&wire, _)) => Err(Rejection::PublicInput { wire }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks a committed proof's shape and public values, then every constraint that only touches
+    /// public wires. The rest are hidden behind the commitments, which are never opened, so they
+    /// are left unchecked and reported in `Verification::PartiallyVerified`.
+    fn verify_committed(
+        &self,
+        proof: &CommittedWitness,
+        public_inputs: &[FieldElement],
+    ) -> Result<Verification, Rejection> {
+        if proof.public_values.len() != self.public_inputs.len() {
+            return Err(Rejection::WitnessSize {
+                found: proof.public_values.len(),
+                expected: self.public_inputs.len(),
+            });
+        }
+        let mut witness = vec![FieldElement::zero(); self.wires.len()];
+        witness[Self::ONE] = FieldElement::one();
+        for (&wire, value) in self.public_inputs.iter().zip(&proof.public_values) {
+            witness[wire] = value.clone();
+        }
+        self.check_public_values(public_inputs, |wire| &witness[wire])?;
+        if proof.commitments.len() != self.private_wires().len() {
+            return Err(Rejection::CommitmentCount {
+                found: proof.commitments.len(),
+                expected: self.private_wires().len(),
+            });
+        }
+
+        let known: HashSet<usize> = self
+            .public_inputs
+            .iter()
+            .copied()
+            .chain([Self::ONE])
+            .collect();
+
+        let mut r1cs = self.synthesize().assign(&witness);
+        let n_constraints = r1cs.constraints.len();
+        r1cs.constraints.retain(|constraint| {
+            let third: &[(Variable, FieldElement)] = match &constraint.operation {
+                Operation::Hash3(third) => third,
+                _ => &[],
+            };
+            constraint
+                .left
+                .iter()
+                .chain(&constraint.right)
+                .chain(&constraint.output)
+                .chain(third)
+                .all(|(variable, _)| known.contains(&variable.index))
+        });
+        r1cs.check(|inputs| self.apply_hash_many(inputs))
+            .map_err(Rejection::Constraint)?;
+
+        let unchecked_constraints = n_constraints - r1cs.constraints.len();
+        Ok(match unchecked_constraints {
+            0 => Verification::Valid,
+            _ => Verification::PartiallyVerified {
+                unchecked_constraints,
+            },
+        })
+    }
+
+    /// Writes the constraints in circom's `.r1cs` binary format so snarkjs can set up and prove the circuit.
+    ///
+    /// Wire 0 is the constant one in both systems; the circuit's other wires follow in circom's order:
+    /// public inputs (as declared), private inputs, then allocated wires. The label section maps each
+    /// exported wire back to its index in this circuit. Hash gates have no arithmetic form and are rejected.
+    pub fn export_r1cs(&self, path: &str) -> Result<(), ExportError> {
+        self.validate()?;
+        let template = self.synthesize();
+
+        let (order, n_private_inputs) = self.circom_wire_order();
+        let mut circom_wire = vec![0u32; self.wires.len()];
+        for (position, &wire) in order.iter().enumerate() {
+            circom_wire[wire] = position as u32;
+        }
+
+        let one = FieldElement::one();
+        let minus_one = -FieldElement::one();
+        let mut constraints = Vec::new();
+        for (index, constraint) in template.constraints.iter().enumerate() {
+            // circom constraints read A * B - C = 0
+            let (mut a, mut b, mut c) = (BTreeMap::new(), BTreeMap::new(), BTreeMap::new());
+            match &constraint.operation {
+                Operation::Mul => {
+                    accumulate(&mut a, &constraint.left, &one, &circom_wire);
+                    accumulate(&mut b, &constraint.right, &one, &circom_wire);
+                    accumulate(&mut c, &constraint.output, &one, &circom_wire);
+                }
+                // Linear constraints multiply by the constant one wire: (left ± right) * 1 = output
+                Operation::Add | Operation::Sub => {
+                    let right_sign = match constraint.operation {
+                        Operation::Add => &one,
+                        _ => &minus_one,
+                    };
+                    accumulate(&mut a, &constraint.left, &one, &circom_wire);
+                    accumulate(&mut a, &constraint.right, right_sign, &circom_wire);
+                    b.insert(0, one.clone());
+                    accumulate(&mut c, &constraint.output, &one, &circom_wire);
+                }
+                Operation::EqualConstant(constant) => {
+                    accumulate(&mut c, &constraint.left, &one, &circom_wire);
+                    let entry = c.entry(0).or_insert_with(FieldElement::zero);
+                    *entry -= constant;
+                }
+                Operation::Hash | Operation::Hash3(_) | Operation::Hash1 => {
+                    return Err(ExportError::UnsupportedConstraint {
+                        index,
+                        label: constraint.label.clone(),
+                    });
+                }
+            }
+            for lc in [&a, &b, &c] {
+                write_linear_combination(&mut constraints, lc);
+            }
+        }
+
+        let n_wires = self.wires.len() as u32;
+        let mut header = Vec::new();
+        header.extend_from_slice(&32u32.to_le_bytes()); // field size in bytes
+        header.extend_from_slice(&prime_bytes());
+        header.extend_from_slice(&n_wires.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes()); // public outputs
+        header.extend_from_slice(&(self.public_inputs.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(n_private_inputs as u32).to_le_bytes());
+        header.extend_from_slice(&(n_wires as u64).to_le_bytes()); // labels
+        header.extend_from_slice(&(template.constraints.len() as u32).to_le_bytes());
+
+        let mut labels = Vec::new();
+        for wire in &order {
+            labels.extend_from_slice(&(*wire as u64).to_le_bytes());
+        }
+
+        let file = circom_file(b"r1cs", 1, [header, constraints, labels]);
+        std::fs::write(path, file)?;
+        Ok(())
+    }
+
+    /// Writes the computed witness in snarkjs' `.wtns` format.
+    /// Values follow the same wire order as `export_r1cs`, starting with the constant one, so the two files pair up.
+    pub fn export_witness(&self, path: &str) -> Result<(), ExportError> {
+        self.validate()?;
+        let witness = self.compute_witness()?;
+        let (order, _) = self.circom_wire_order();
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&32u32.to_le_bytes()); // field size in bytes
+        header.extend_from_slice(&prime_bytes());
+        header.extend_from_slice(&(order.len() as u32).to_le_bytes());
+
+        let mut values = Vec::new();
+        for wire in &order {
+            values.extend_from_slice(&witness[*wire].to_bytes_le());
+        }
+
+        let file = circom_file(b"wtns", 2, [header, values]);
+        std::fs::write(path, file)?;
+        Ok(())
+    }
+
+    /// Wire order circom expects: the constant one, public inputs (as declared), private inputs,
+    /// then allocated wires. Also returns how many private inputs there are.
+    fn circom_wire_order(&self) -> (Vec<usize>, usize) {
+        let private_inputs: Vec<usize> = (Self::ONE + 1..self.wires.len())
+            .filter(|index| self.is_input(*index) && !self.public_inputs.contains(index))
+            .collect();
+        let n_private_inputs = private_inputs.len();
+        let internal = (0..self.wires.len())
+            .filter(|index| !self.is_input(*index) && !self.public_inputs.contains(index));
+        let order = [Self::ONE]
+            .into_iter()
+            .chain(self.public_inputs.iter().copied())
+            .chain(private_inputs)
+            .chain(internal)
+            .collect();
+        (order, n_private_inputs)
+    }
+}
+
+/// The BN254 prime as circom writes it: 32 bytes, little-endian
+fn prime_bytes() -> [u8; 32] {
+    let modulus = Bn254::modulus().to_bytes_le().1;
+    let mut prime = [0u8; 32];
+    prime[..modulus.len()].copy_from_slice(&modulus);
+    prime
+}
+
+/// Lays out a circom binary file: magic, version, then numbered sections as `type, u64 size, data`
+fn circom_file<const N: usize>(magic: &[u8; 4], version: u32, sections: [Vec<u8>; N]) -> Vec<u8> {
+    let mut file = Vec::new();
+    file.extend_from_slice(magic);
+    file.extend_from_slice(&version.to_le_bytes());
+    file.extend_from_slice(&(N as u32).to_le_bytes());
+    for (section_type, section) in sections.iter().enumerate() {
+        file.extend_from_slice(&(section_type as u32 + 1).to_le_bytes());
+        file.extend_from_slice(&(section.len() as u64).to_le_bytes());
+        file.extend_from_slice(section);
+    }
+    file
+}
+
+/// Adds `sign * terms` to a circom linear combination keyed by exported wire id
+fn accumulate(
+    lc: &mut BTreeMap<u32, FieldElement>,
+    terms: &[(Variable, FieldElement)],
+    sign: &FieldElement,
+    circom_wire: &[u32],
+) {
+    for (variable, coeff) in terms {
+        let entry = lc
+            .entry(circom_wire[variable.index])
+            .or_insert_with(FieldElement::zero);
+        *entry += coeff * sign;
+    }
+}
+
+/// Appends a linear combination as `n, (wire, coeff)*`, skipping terms that cancelled out
+fn write_linear_combination(out: &mut Vec<u8>, lc: &BTreeMap<u32, FieldElement>) {
+    let terms: Vec<_> = lc.iter().filter(|(_, coeff)| !coeff.is_zero()).collect();
+    out.extend_from_slice(&(terms.len() as u32).to_le_bytes());
+    for (wire, coeff) in terms {
+        out.extend_from_slice(&wire.to_le_bytes());
+        out.extend_from_slice(&coeff.to_bytes_le());
+    }
+}
+
+/// `c1*w1 + c2*w2 + ...` for `Gate`'s `Display`
+fn format_terms(terms: &[(usize, FieldElement)]) -> String {
+    let terms: Vec<String> = terms
+        .iter()
+        .map(|(wire, coeff)| match coeff.try_to_i64() {
+            // Small coefficients read better signed than as their field representative
+            Some(small) => format!("{}*{}", small, wire),
+            None => format!("{}*{}", coeff, wire),
+        })
+        .collect();
+    terms.join(" + ")
+}
+
+/// bincode size of a `ProofHeader`: magic, version and digest, with no length prefixes
+const PROOF_HEADER_SIZE: usize = 4 + 4 + 32;
+
+/// bincode size of a reduced BN254 element: a sign byte, a u64 digit count and up to 8 u32 digits
+const MAX_ENCODED_FIELD_SIZE: usize = 1 + 8 + 4 * 8;
+
+/// bincode size of `value`, which is encoded as its `BigInt`
+fn encoded_field_size(value: &FieldElement) -> usize {
+    1 + 8 + 4 * value.value.bits().div_ceil(32) as usize
+}
+
+/// bincode size of an optional label: a tag byte, then a u64 length and the UTF-8 bytes
+fn encoded_label_size(label: &Option<String>) -> usize {
+    1 + label.as_ref().map_or(0, |label| 8 + label.len())
+}
+
+/// `wire * (wire - 1) = 0`, which only 0 and 1 satisfy
+fn boolean_constraint(r1cs: &mut R1CS, wire: usize) {
+    r1cs.add_constraint(
+        vec![(r1cs.variables[wire].clone(), FieldElement::one())],
+        vec![
+            (r1cs.variables[wire].clone(), FieldElement::one()),
+            (r1cs.variables[Circuit::ONE].clone(), -FieldElement::one()),
+        ],
+        vec![],
+        Operation::Mul,
+    );
+}
+
+/// Reads a wire during evaluation; gates must only consume wires that are already known
+fn wire_value(values: &WitnessValues, index: usize) -> Result<FieldElement, CircuitError> {
+    values
+        .get(index)
+        .cloned()
+        .ok_or(CircuitError::ReadBeforeAssign(index))
+}
+
+/// Stores a gate's computed value, unless the wire was already given one by the caller
+fn fill_wire(values: &mut WitnessValues, index: usize, value: FieldElement) {
+    values.fill(index, value);
+}
+
+/// Field values of a packed bit, so a bit can be lent out like any stored value
+static BIT_VALUES: LazyLock<[FieldElement; 2]> =
+    LazyLock::new(|| [FieldElement::zero(), FieldElement::one()]);
+
+/// Set of wire indices, one bit per wire
+#[derive(Clone, Debug, Default, PartialEq)]
+struct WireSet {
+    words: Vec<u64>,
+}
+
+impl WireSet {
+    fn insert(&mut self, index: usize) {
+        let word = index / 64;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << (index % 64);
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.words
+            .get(index / 64)
+            .is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+
+    #[cfg(test)]
+    fn heap_bytes(&self) -> usize {
+        8 * self.words.capacity()
+    }
+}
+
+/// Two bitsets indexed by wire: which wires are packed booleans, and the bit each one holds
+#[derive(Clone, Debug, Default, PartialEq)]
+struct PackedBooleans {
+    present: WireSet,
+    values: WireSet,
+}
+
+impl PackedBooleans {
+    fn insert(&mut self, index: usize, value: bool) {
+        self.present.insert(index);
+        if value {
+            self.values.insert(index);
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<bool> {
+        self.present
+            .contains(index)
+            .then(|| self.values.contains(index))
+    }
+
+    #[cfg(test)]
+    fn heap_bytes(&self) -> usize {
+        self.present.heap_bytes() + self.values.heap_bytes()
+    }
+}
+
+/// Wire values while the gates are evaluated. `add_boolean_packed` inputs and the gadget bits
+/// marked with `alloc_packed_bit` live in a bitset; every other wire has its usual
+/// `Option<FieldElement>` slot. `into_witness` turns the bits into field elements at the end.
+struct WitnessValues<'a> {
+    values: Vec<Option<FieldElement>>, // `None` for bits and for wires not assigned yet
+    bits: PackedBooleans,
+    packed_outputs: &'a WireSet,
+}
+
+impl<'a> WitnessValues<'a> {
+    /// Starts from the circuit's inputs
+    fn new(circuit: &'a Circuit) -> Self {
+        Self {
+            values: circuit.wires.clone(),
+            bits: circuit.packed_booleans.clone(),
+            packed_outputs: &circuit.packed_outputs,
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<&FieldElement> {
+        match self.bits.get(index) {
+            Some(bit) => Some(&BIT_VALUES[bit as usize]),
+            None => self.values[index].as_ref(),
+        }
+    }
+
+    /// Assigns `index`, unless it already has a value
+    fn fill(&mut self, index: usize, value: FieldElement) {
+        if self.get(index).is_some() {
+            return;
+        }
+        if self.packed_outputs.contains(index) {
+            debug_assert!(
+                value.is_zero() || value.is_one(),
+                "packed wire {} is not a bit",
+                index
+            );
+            self.bits.insert(index, value.is_one());
+        } else {
+            self.values[index] = Some(value);
+        }
+    }
+
+    /// Every wire's value in wire order, or the first wire no gate assigned
+    fn into_witness(self) -> Result<Vec<FieldElement>, CircuitError> {
+        (0..self.values.len())
+            .map(|index| {
+                self.get(index)
+                    .cloned()
+                    .ok_or(CircuitError::UnassignedWire(index))
+            })
+            .collect()
+    }
+
+    /// Heap bytes held: the value slots with the digits of every stored field element, and the bitsets
+    #[cfg(test)]
+    fn heap_bytes(&self) -> usize {
+        let digits: usize = self
+            .values
+            .iter()
+            .flatten()
+            .map(|value| value.value.iter_u64_digits().len() * 8)
+            .sum();
+        self.values.capacity() * std::mem::size_of::<Option<FieldElement>>()
+            + digits
+            + self.bits.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+    use crate::r1cs::ConstraintError;
+
+    fn fe(value: i64) -> FieldElement {
+        FieldElement::from_i64(value)
+    }
+
+    /// A fresh path in the system temp directory, so parallel tests never share a proof file
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("mini_zk_{}_{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Serializes `r1cs` behind a header carrying `circuit`'s digest, like `generate_proof` does
+    fn forged_proof(circuit: &Circuit, r1cs: &R1CS) -> Vec<u8> {
+        let header = ProofHeader {
+            magic: PROOF_MAGIC,
+            version: PROOF_VERSION,
+            circuit_digest: circuit.digest(),
+        };
+        let mut file = bincode::serialize(&header).unwrap();
+        file.extend(bincode::serialize(r1cs).unwrap());
+        file
+    }
+
+    /// `a * b = product` with the product public
+    fn product_circuit(a: i64, b: i64, product: i64) -> Circuit {
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(a));
+        let b = circuit.add_input(fe(b));
+        let product = circuit.add_public_input(fe(product));
+        circuit.add_gate(Gate::Mul(a, b, product));
+        circuit
+    }
+
+    /// Proves `circuit` into a temp file, then verifies the file against the circuit's own public
+    /// inputs. Returns what `generate_proof` and `verify_proof` reported.
+    fn prove_and_verify(circuit: &Circuit, name: &str) -> (bool, Verification) {
+        let path = temp_path(name);
+        let satisfied = circuit.generate_proof(&path).unwrap();
+        let public_inputs = circuit.public_inputs().unwrap();
+        let verification = circuit.verify_proof(&path, &public_inputs).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        (satisfied, verification)
+    }
+
+    /// Evaluates `circuit` and reports whether its own constraints hold
+    fn holds(circuit: &Circuit) -> bool {
+        let r1cs = circuit.evaluate().unwrap();
+        r1cs.check(|inputs| circuit.apply_hash_many(inputs)).is_ok()
+    }
+
+    #[test]
+    fn allocated_add_output_is_computed() {
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(10));
+        let b = circuit.add_input(fe(20));
+        let sum = circuit.alloc_wire();
+        circuit.add_gate(Gate::Add(a, b, sum));
+
+        let r1cs = circuit.evaluate().unwrap();
+        assert_eq!(r1cs.variables[sum].value, fe(30));
+        assert!(r1cs.check(|inputs| circuit.apply_hash_many(inputs)).is_ok());
+    }
+
+    #[test]
+    fn allocated_wire_without_a_gate_is_an_error() {
+        let mut circuit = Circuit::new(None);
+        let orphan = circuit.alloc_wire();
+        assert_eq!(
+            circuit.evaluate().err(),
+            Some(CircuitError::UnassignedWire(orphan))
+        );
+    }
+
+    #[test]
+    fn reading_a_wire_before_its_gate_is_an_error() {
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(2));
+        let (early, late) = (circuit.alloc_wire(), circuit.alloc_wire());
+        circuit.add_gate(Gate::Mul(a, late, early));
+        circuit.add_gate(Gate::Add(a, a, late));
+        assert_eq!(
+            circuit.evaluate().err(),
+            Some(CircuitError::ReadBeforeAssign(late))
+        );
+    }
+
+    #[test]
+    fn evaluate_binds_the_witness_whether_or_not_it_satisfies() {
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(3));
+        let b = circuit.add_input(fe(4));
+        let product = circuit.alloc_wire();
+        circuit.add_gate(Gate::Mul(a, b, product));
+        let claimed = circuit.add_public_input(fe(12));
+        circuit.add_gate(Gate::AssertEqual(product, claimed));
+
+        let r1cs = circuit.evaluate().unwrap();
+        assert_eq!(r1cs.variables[product].value, fe(12));
+        assert!(r1cs.check(|inputs| circuit.apply_hash_many(inputs)).is_ok());
+
+        let mut wrong = Circuit::new(None);
+        let a = wrong.add_input(fe(3));
+        let b = wrong.add_input(fe(4));
+        let product = wrong.alloc_wire();
+        wrong.add_gate(Gate::Mul(a, b, product));
+        let claimed = wrong.add_public_input(fe(13));
+        wrong.add_gate(Gate::AssertEqual(product, claimed));
+
+        let r1cs = wrong.evaluate().unwrap();
+        assert_eq!(r1cs.variables[product].value, fe(12));
+        assert_eq!(
+            r1cs.all_failures(|inputs| wrong.apply_hash_many(inputs))
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn evaluate_reports_invalid_circuits_as_errors() {
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(1));
+        circuit.add_gate(Gate::Add(a, a, 99));
+        assert_eq!(
+            circuit.evaluate().err(),
+            Some(CircuitError::InvalidWire {
+                gate_index: 0,
+                wire: 99
+            })
+        );
+
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(1));
+        let out = circuit.alloc_wire();
+        circuit.add_gate(Gate::Hash(a, a, out));
+        assert_eq!(
+            circuit.evaluate().err(),
+            Some(CircuitError::MissingHashFunction { gate_index: 0 })
+        );
+    }
+
+    #[test]
+    fn honest_proof_verifies() {
+        let circuit = product_circuit(3, 4, 12);
+        let path = temp_path("honest.bin");
+        assert!(circuit.generate_proof(&path).unwrap());
+        assert_eq!(
+            circuit.verify_proof(&path, &[fe(12)]).unwrap(),
+            Verification::Valid
+        );
+        assert_eq!(
+            circuit.verify_proof(&path, &[fe(13)]).unwrap(),
+            Verification::Rejected(Rejection::PublicInput { wire: 3 })
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn forged_proof_without_constraints_is_rejected() {
+        let circuit = product_circuit(3, 4, 12);
+        let mut r1cs = circuit.evaluate().unwrap();
+        r1cs.variables[1].value = fe(5); // 5 * 4 != 12
+        r1cs.constraints.clear();
+
+        let file = forged_proof(&circuit, &r1cs);
+        assert!(matches!(
+            circuit.verify_proof_reader(&file[..], &[fe(12)]).unwrap(),
+            Verification::Rejected(Rejection::Constraint(ConstraintError::Unsatisfied(_)))
+        ));
+    }
+
+    #[test]
+    fn tampered_witness_is_rejected() {
+        let circuit = product_circuit(3, 4, 12);
+        let mut r1cs = circuit.evaluate().unwrap();
+        r1cs.variables[2].value = fe(6);
+
+        let file = forged_proof(&circuit, &r1cs);
+        assert!(matches!(
+            circuit.verify_proof_reader(&file[..], &[fe(12)]).unwrap(),
+            Verification::Rejected(Rejection::Constraint(ConstraintError::Unsatisfied(_)))
+        ));
+    }
+
+    #[test]
+    fn proof_with_missing_wires_is_rejected() {
+        let circuit = product_circuit(3, 4, 12);
+        let mut r1cs = circuit.evaluate().unwrap();
+        r1cs.variables.pop();
+
+        let file = forged_proof(&circuit, &r1cs);
+        assert_eq!(
+            circuit.verify_proof_reader(&file[..], &[fe(12)]).unwrap(),
+            Verification::Rejected(Rejection::WitnessSize {
+                found: 3,
+                expected: 4
+            })
+        );
+    }
+
+    #[test]
+    fn header_version_mismatch_is_an_error() {
+        let circuit = product_circuit(3, 4, 12);
+        let mut file = forged_proof(&circuit, &circuit.evaluate().unwrap());
+        file[4..8].copy_from_slice(&(PROOF_VERSION + 1).to_le_bytes());
+
+        match circuit.verify_proof_reader(&file[..], &[fe(12)]) {
+            Err(ProofError::UnsupportedVersion { found, expected }) => {
+                assert_eq!((found, expected), (PROOF_VERSION + 1, PROOF_VERSION));
+            }
+            other => panic!("expected UnsupportedVersion, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn header_digest_mismatch_is_an_error() {
+        let circuit = product_circuit(3, 4, 12);
+        let path = temp_path("digest_mismatch.bin");
+        circuit.generate_proof(&path).unwrap();
+
+        let mut other = product_circuit(3, 4, 12);
+        other.add_gate(Gate::Boolean(1));
+        assert!(matches!(
+            other.verify_proof(&path, &[fe(12)]),
+            Err(ProofError::CircuitMismatch)
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn v1_verdict_files_are_unverifiable() {
+        let circuit = product_circuit(3, 4, 12);
+        let path = temp_path("v1.bin");
+        std::fs::write(&path, [1u8]).unwrap();
+
+        assert!(matches!(
+            circuit.verify_proof(&path, &[fe(12)]),
+            Err(ProofError::UnverifiableFormat)
+        ));
+        assert!(matches!(
+            r1cs::verify_proof_file(&path, &PoseidonHash::new()),
+            Err(ProofError::UnverifiableFormat)
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn v2_files_are_checked_against_the_circuit() {
+        let circuit = product_circuit(3, 4, 12);
+        let honest = bincode::serialize(&circuit.evaluate().unwrap()).unwrap();
+        assert_eq!(
+            circuit.verify_proof_reader(&honest[..], &[fe(12)]).unwrap(),
+            Verification::Valid
+        );
+
+        // A bare R1CS for a system of the prover's choosing: 5 * 4 = 20, with 12 on the public wire
+        let unrelated = product_circuit(5, 4, 20);
+        let mut r1cs = unrelated.evaluate().unwrap();
+        r1cs.variables[3].value = fe(12);
+        r1cs.constraints.clear();
+        let forged = bincode::serialize(&r1cs).unwrap();
+        assert!(matches!(
+            circuit.verify_proof_reader(&forged[..], &[fe(12)]).unwrap(),
+            Verification::Rejected(Rejection::Constraint(_))
+        ));
+    }
+
+    #[test]
+    fn committed_proof_hides_private_values() {
+        let secret = FieldElement::random_seeded(72);
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(secret.clone());
+        let square = circuit.add_public_input(&secret * &secret);
+        circuit.add_gate(Gate::Square(a, square));
+
+        let path = temp_path("committed.bin");
+        let blindings = [FieldElement::random_seeded(1)];
+        assert!(circuit.generate_committed_proof(&path, &blindings).unwrap());
+        let file = std::fs::read(&path).unwrap();
+        let contains = |needle: &[u8]| file.windows(needle.len()).any(|window| window == needle);
+        assert!(!contains(&secret.to_bytes_le()));
+        assert!(!contains(&bincode::serialize(&secret).unwrap()));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn committed_proof_is_only_partially_verified() {
+        // 5 * 4 != 12, but the only constraint touches private wires and is never checked
+        let circuit = product_circuit(5, 4, 12);
+        let path = temp_path("committed_unsatisfied.bin");
+        let blindings = [
+            FieldElement::random_seeded(1),
+            FieldElement::random_seeded(2),
+        ];
+        assert!(!circuit.generate_committed_proof(&path, &blindings).unwrap());
+
+        assert_eq!(
+            circuit.verify_proof(&path, &[fe(12)]).unwrap(),
+            Verification::PartiallyVerified {
+                unchecked_constraints: 1
+            }
+        );
+        assert_eq!(
+            circuit.verify_proof(&path, &[fe(13)]).unwrap(),
+            Verification::Rejected(Rejection::PublicInput { wire: 3 })
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn committed_proof_checks_public_only_constraints() {
+        let mut circuit = Circuit::new(None);
+        let hidden = circuit.add_input(fe(7));
+        let x = circuit.add_public_input(fe(2));
+        let y = circuit.add_public_input(fe(5));
+        circuit.add_gate(Gate::Add(x, x, y)); // 2 + 2 != 5, visible to the verifier
+        circuit.add_gate(Gate::Boolean(hidden));
+
+        let path = temp_path("committed_public.bin");
+        circuit
+            .generate_committed_proof(&path, &[FieldElement::random_seeded(3)])
+            .unwrap();
+        assert!(matches!(
+            circuit.verify_proof(&path, &[fe(2), fe(5)]).unwrap(),
+            Verification::Rejected(Rejection::Constraint(_))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn stats_of_the_addition_example() {
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(10));
+        let b = circuit.add_input(fe(20));
+        let sum = circuit.add_public_input(fe(30));
+        circuit.add_gate(Gate::Add(a, b, sum));
+
+        assert_eq!(circuit.num_gates(), 1);
+        assert_eq!(circuit.num_wires(), 4);
+        let stats = circuit.stats().unwrap();
+        assert_eq!(stats.num_variables, 4);
+        assert_eq!(stats.num_constraints, 1);
+        assert_eq!(stats.add, 1);
+        assert_eq!(
+            stats.mul + stats.sub + stats.hash + stats.hash3 + stats.hash1 + stats.equal_constant,
+            0
+        );
+        assert_eq!(
+            stats.to_string(),
+            "4 variables, 1 constraints\n  add: 1, sub: 0, mul: 0, hash: 0, hash3: 0, hash1: 0, equal_constant: 0"
+        );
+    }
+
+    #[test]
+    fn stats_of_the_merkle_example() {
+        let leaves: Vec<FieldElement> = [1001, 2002, 3003, 4004].map(fe).to_vec();
+        let tree = MerkleTree::new(leaves.clone());
+        let mut circuit = Circuit::new(Some(Box::new(PoseidonHash::new())));
+        let leaf = circuit.add_input(leaves[1].clone());
+        let (mut path, mut bits) = (Vec::new(), Vec::new());
+        for (sibling, is_right) in tree.get_proof_with_directions(1) {
+            path.push(circuit.add_input(sibling));
+            bits.push(circuit.add_input(fe(is_right as i64)));
+        }
+        let root = circuit.add_public_input(tree.get_root());
+        circuit.add_merkle_path_verify(leaf, &path, &bits, root);
+
+        // Two levels of conditional swap and hash, then the root comparison
+        assert_eq!(circuit.num_gates(), 5);
+        assert_eq!(circuit.num_wires(), 13);
+        let stats = circuit.stats().unwrap();
+        assert_eq!(
+            stats,
+            R1csStats {
+                num_variables: 13,
+                num_constraints: 9,
+                add: 2,
+                sub: 1,
+                mul: 4,
+                hash: 2,
+                hash3: 0,
+                hash1: 0,
+                equal_constant: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn stats_of_an_invalid_circuit_is_an_error() {
+        let mut circuit = Circuit::new(None);
+        circuit.add_gate(Gate::Add(Circuit::ONE, Circuit::ONE, 5));
+        assert_eq!(
+            circuit.stats(),
+            Err(CircuitError::InvalidWire {
+                gate_index: 0,
+                wire: 5
+            })
+        );
+    }
+
+    #[test]
+    fn estimated_proof_size_matches_the_file() {
+        let circuit = product_circuit(3, 4, 12);
+        let path = temp_path("estimate.bin");
+        circuit.generate_proof(&path).unwrap();
+        let actual = std::fs::metadata(&path).unwrap().len() as usize;
+        assert_eq!(circuit.estimated_proof_size().unwrap(), actual);
+        std::fs::remove_file(path).unwrap();
+
+        // The orphan wire has no value, so it is sized at full width
+        let mut unassigned = Circuit::new(None);
+        unassigned.alloc_wire();
+        let mut assigned = Circuit::new(None);
+        assigned.add_input(fe(0));
+        assert!(
+            unassigned.estimated_proof_size().unwrap() > assigned.estimated_proof_size().unwrap()
+        );
+    }
+
+    #[test]
+    fn estimated_proof_size_of_an_invalid_circuit_is_an_error() {
+        let mut circuit = Circuit::new(None);
+        circuit.add_gate(Gate::Square(Circuit::ONE, 7));
+        assert_eq!(
+            circuit.estimated_proof_size(),
+            Err(CircuitError::InvalidWire {
+                gate_index: 0,
+                wire: 7
+            })
+        );
+    }
+
+    /// Proves the note `(secret, blinding)` at `index` of `notes` is in the tree; returns the
+    /// circuit and the nullifier wire
+    fn nullifier_circuit(
+        notes: &[(FieldElement, FieldElement)],
+        index: usize,
+        claimed_blinding: FieldElement,
+    ) -> (Circuit, MerkleTree, usize) {
+        let hasher = PoseidonHash::new();
+        let leaves = notes
+            .iter()
+            .map(|(secret, blinding)| hasher.hash(secret, blinding))
+            .collect();
+        let tree = MerkleTree::new(leaves);
+
+        let mut circuit = Circuit::new(Some(Box::new(PoseidonHash::new())));
+        let secret = circuit.add_input(notes[index].0.clone());
+        let blinding = circuit.add_input(claimed_blinding);
+        let (mut path, mut bits) = (Vec::new(), Vec::new());
+        for (sibling, is_right) in tree.get_proof_with_directions(index) {
+            path.push(circuit.add_input(sibling));
+            bits.push(circuit.add_input(fe(is_right as i64)));
+        }
+        let root = circuit.add_public_input(tree.get_root());
+        let nullifier = circuit.add_nullifier_membership(secret, blinding, &path, &bits, root);
+        (circuit, tree, nullifier)
+    }
+
+    #[test]
+    fn nullifier_membership_proves_and_hides_the_leaf() {
+        let notes: Vec<_> = (0..4)
+            .map(|i| (FieldElement::random_seeded(i), fe(0)))
+            .collect();
+        let (circuit, tree, nullifier) = nullifier_circuit(&notes, 2, fe(0));
+
+        let path = temp_path("nullifier.bin");
+        assert!(circuit.generate_proof(&path).unwrap());
+        let expected_nullifier = PoseidonHash::new().hash_one(&notes[2].0);
+        assert_eq!(
+            circuit.get_wire(nullifier),
+            Some(expected_nullifier.clone())
+        );
+        assert_eq!(circuit.stats().unwrap().hash1, 1);
+        // Even with a zero blinding the nullifier is not the leaf it was spent from
+        assert!(!tree.iter_leaves().any(|leaf| *leaf == expected_nullifier));
+        assert_eq!(
+            circuit
+                .verify_proof(&path, &[tree.get_root(), expected_nullifier])
+                .unwrap(),
+            Verification::Valid
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn nullifier_membership_rejects_a_wrong_blinding() {
+        let notes: Vec<_> = (0..4)
+            .map(|i| {
+                (
+                    FieldElement::random_seeded(i),
+                    FieldElement::random_seeded(10 + i),
+                )
+            })
+            .collect();
+        let (circuit, _, _) = nullifier_circuit(&notes, 1, fe(5));
+        let r1cs = circuit.evaluate().unwrap();
+        assert!(
+            r1cs.check(|inputs| circuit.apply_hash_many(inputs))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn public_inputs_evaluates_computed_public_wires() {
+        let notes: Vec<_> = (0..2)
+            .map(|i| {
+                (
+                    FieldElement::random_seeded(i),
+                    FieldElement::random_seeded(5 + i),
+                )
+            })
+            .collect();
+        let (circuit, tree, _) = nullifier_circuit(&notes, 0, notes[0].1.clone());
+
+        // Nothing evaluated yet: the nullifier is computed on demand
+        let public = circuit.public_inputs().unwrap();
+        let nullifier = PoseidonHash::new().hash_one(&notes[0].0);
+        assert_eq!(public, vec![tree.get_root(), nullifier]);
+
+        let path = temp_path("public_inputs.bin");
+        circuit.generate_proof(&path).unwrap();
+        assert_eq!(
+            circuit.verify_proof(&path, &public).unwrap(),
+            Verification::Valid
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn public_inputs_reports_evaluation_errors() {
+        let mut circuit = Circuit::new(None);
+        let x = circuit.add_input(fe(3));
+        let out = circuit.alloc_wire();
+        circuit.add_gate(Gate::Hash1(x, out));
+        circuit.public_inputs.push(out);
+        assert_eq!(
+            circuit.public_inputs(),
+            Err(CircuitError::MissingHashFunction { gate_index: 0 })
+        );
+    }
+
+    /// `x` rebuilt from 253 input bits and range-checked to 253 bits, the widest check the field
+    /// allows: `add_range_check` needs `n_bits < 254`, since a 254-bit or wider decomposition
+    /// (let alone the 256 bits of a `u256`) is no longer unique modulo the 254-bit prime.
+    fn bit_recomposition_circuit(x: &FieldElement, packed: bool) -> Circuit {
+        const N_BITS: u64 = 253;
+        let mut circuit = Circuit::new(None);
+        let x_wire = circuit.add_public_input(x.clone());
+        let terms = (0..N_BITS)
+            .map(|i| {
+                let bit_value = x.value.bit(i);
+                let bit = if packed {
+                    circuit.add_boolean_packed(bit_value)
+                } else {
+                    let bit = circuit.add_input(fe(bit_value as i64));
+                    circuit.add_boolean(bit);
+                    bit
+                };
+                (bit, FieldElement::from_i32(2).pow_u64(i))
+            })
+            .collect();
+        let recomposed = circuit.alloc_wire();
+        circuit.add_gate(Gate::LinearCombination {
+            terms,
+            output: recomposed,
+        });
+        circuit.add_gate(Gate::AssertEqual(recomposed, x_wire));
+        circuit.add_range_check(x_wire, N_BITS as usize);
+        circuit
+    }
+
+    #[test]
+    fn packed_range_check_proves_with_less_memory_than_unpacked() {
+        // Mostly one bits: an unpacked 1 stores a heap digit, a packed one only its bit
+        let x = FieldElement::from_i32(2).pow_u64(253) - fe(12345);
+        let packed = bit_recomposition_circuit(&x, true);
+        let unpacked = bit_recomposition_circuit(&x, false);
+
+        for (circuit, name) in [
+            (&packed, "packed_bits.bin"),
+            (&unpacked, "unpacked_bits.bin"),
+        ] {
+            assert_eq!(prove_and_verify(circuit, name), (true, Verification::Valid));
+        }
+        assert_eq!(
+            packed.evaluate().unwrap().variables,
+            unpacked.evaluate().unwrap().variables
+        );
+
+        // Both keep the range check's 253 gadget bits packed; only the inputs differ
+        let packed_bytes = packed.evaluate_wires().unwrap().heap_bytes();
+        let unpacked_bytes = unpacked.evaluate_wires().unwrap().heap_bytes();
+        assert!(
+            packed_bytes < unpacked_bytes,
+            "packed {} bytes, unpacked {} bytes",
+            packed_bytes,
+            unpacked_bytes
+        );
+    }
+
+    #[test]
+    fn sub_gate_proves_30_minus_20() {
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(30));
+        let b = circuit.add_input(fe(20));
+        let difference = circuit.alloc_wire();
+        circuit.add_gate(Gate::Sub(a, b, difference));
+        let expected = circuit.add_public_input(fe(10));
+        circuit.add_gate(Gate::AssertEqual(difference, expected));
+
+        assert_eq!(
+            prove_and_verify(&circuit, "sub.bin"),
+            (true, Verification::Valid)
+        );
+        assert_eq!(circuit.get_wire(difference), Some(fe(10)));
+
+        let mut wrong = Circuit::new(None);
+        let a = wrong.add_input(fe(30));
+        let b = wrong.add_input(fe(20));
+        let difference = wrong.add_input(fe(11));
+        wrong.add_gate(Gate::Sub(a, b, difference));
+        assert!(!wrong.generate_proof(&temp_path("sub_wrong.bin")).unwrap());
+        std::fs::remove_file(temp_path("sub_wrong.bin")).unwrap();
+    }
+
+    #[test]
+    fn assert_equal_constant_rejects_the_wrong_constant() {
+        let sum_circuit = |constant: i64| {
+            let mut circuit = Circuit::new(None);
+            let a = circuit.add_input(fe(10));
+            let b = circuit.add_input(fe(20));
+            let sum = circuit.alloc_wire();
+            circuit.add_gate(Gate::Add(a, b, sum));
+            circuit.add_gate(Gate::AssertEqualConstant(sum, fe(constant)));
+            circuit
+        };
+
+        assert_eq!(
+            prove_and_verify(&sum_circuit(30), "constant.bin"),
+            (true, Verification::Valid)
+        );
+        let (satisfied, verification) = prove_and_verify(&sum_circuit(31), "constant_wrong.bin");
+        assert!(!satisfied);
+        assert!(matches!(
+            verification,
+            Verification::Rejected(Rejection::Constraint(_))
+        ));
+    }
+
+    #[test]
+    fn conditional_swap_follows_the_selector() {
+        for (selector, low, high) in [(0, 3, 8), (1, 8, 3)] {
+            let mut circuit = Circuit::new(None);
+            let a = circuit.add_input(fe(3));
+            let b = circuit.add_input(fe(8));
+            let s = circuit.add_input(fe(selector));
+            let (out_low, out_high) = (circuit.alloc_wire(), circuit.alloc_wire());
+            circuit.add_gate(Gate::ConditionalSwap(a, b, s, out_low, out_high));
+
+            let r1cs = circuit.evaluate().unwrap();
+            assert_eq!(r1cs.variables[out_low].value, fe(low));
+            assert_eq!(r1cs.variables[out_high].value, fe(high));
+            assert!(r1cs.check(|inputs| circuit.apply_hash_many(inputs)).is_ok());
+        }
+
+        let mut non_boolean = Circuit::new(None);
+        let a = non_boolean.add_input(fe(3));
+        let b = non_boolean.add_input(fe(8));
+        let s = non_boolean.add_input(fe(2));
+        let (out_low, out_high) = (non_boolean.alloc_wire(), non_boolean.alloc_wire());
+        non_boolean.add_gate(Gate::ConditionalSwap(a, b, s, out_low, out_high));
+        let r1cs = non_boolean.evaluate().unwrap();
+        assert!(
+            r1cs.check(|inputs| non_boolean.apply_hash_many(inputs))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn proof_io_failures_are_errors() {
+        let circuit = product_circuit(3, 4, 12);
+        let missing = temp_path("no_such_proof.bin");
+        assert!(matches!(
+            circuit.verify_proof(&missing, &[fe(12)]),
+            Err(ProofError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound
+        ));
+
+        let unwritable = std::env::temp_dir()
+            .join(format!("mini_zk_{}_no_such_dir", std::process::id()))
+            .join("proof.bin");
+        assert!(matches!(
+            circuit.generate_proof(&unwritable.to_string_lossy()),
+            Err(ProofError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn different_public_input_is_rejected() {
+        let circuit = product_circuit(3, 4, 12);
+        let path = temp_path("public_input.bin");
+        assert!(circuit.generate_proof(&path).unwrap());
+
+        assert_eq!(
+            circuit.verify_proof(&path, &[fe(12)]).unwrap(),
+            Verification::Valid
+        );
+        assert_eq!(
+            circuit.verify_proof(&path, &[fe(13)]).unwrap(),
+            Verification::Rejected(Rejection::PublicInput { wire: 3 })
+        );
+        assert_eq!(
+            circuit.verify_proof(&path, &[]).unwrap(),
+            Verification::Rejected(Rejection::PublicInputCount {
+                found: 0,
+                expected: 1
+            })
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn failure_message_names_the_gate_and_wires() {
+        let mut circuit = Circuit::new(None);
+        let price = circuit.add_input_named(fe(10), "price");
+        let quantity = circuit.add_input_named(fe(3), "quantity");
+        let total = circuit.add_input_named(fe(31), "total");
+        circuit.add_gate_named(Gate::Mul(price, quantity, total), "order_total");
+
+        let r1cs = circuit.evaluate().unwrap();
+        let error = r1cs
+            .check(|inputs| circuit.apply_hash_many(inputs))
+            .unwrap_err();
+        let message = error.to_string();
+        assert!(
+            message.contains("Constraint #0 [order_total]"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("= 30, while output_value = 31"),
+            "{}",
+            message
+        );
+
+        assert_eq!(r1cs.variables[price].label.as_deref(), Some("price"));
+        assert_eq!(r1cs.constraints[0].label.as_deref(), Some("order_total"));
+    }
+
+    #[test]
+    fn square_gate_proves_7_squared() {
+        let mut circuit = Circuit::new(None);
+        let x = circuit.add_input(fe(7));
+        let square = circuit.alloc_wire();
+        circuit.add_gate(Gate::Square(x, square));
+        let expected = circuit.add_public_input(fe(49));
+        circuit.add_gate(Gate::AssertEqual(square, expected));
+
+        assert_eq!(
+            prove_and_verify(&circuit, "square.bin"),
+            (true, Verification::Valid)
+        );
+        assert_eq!(circuit.get_wire(square), Some(fe(49)));
+
+        let mut wrong = Circuit::new(None);
+        let x = wrong.add_input(fe(7));
+        let square = wrong.add_input(fe(48));
+        wrong.add_gate(Gate::Square(x, square));
+        let r1cs = wrong.evaluate().unwrap();
+        assert!(r1cs.check(|inputs| wrong.apply_hash_many(inputs)).is_err());
+    }
+
+    #[test]
+    fn linear_combination_of_three_weighted_terms() {
+        // 3a + 5b - 2c = d with a = 4, b = 2, c = 5
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(4));
+        let b = circuit.add_input(fe(2));
+        let c = circuit.add_input(fe(5));
+        let d = circuit.add_public_input(fe(12));
+        circuit.add_gate(Gate::LinearCombination {
+            terms: vec![(a, fe(3)), (b, fe(5)), (c, fe(-2))],
+            output: d,
+        });
+
+        assert_eq!(circuit.evaluate().unwrap().constraints.len(), 1);
+        assert_eq!(
+            prove_and_verify(&circuit, "linear_combination.bin"),
+            (true, Verification::Valid)
+        );
+
+        let mut computed = Circuit::new(None);
+        let a = computed.add_input(fe(4));
+        let b = computed.add_input(fe(2));
+        let c = computed.add_input(fe(5));
+        let d = computed.alloc_wire();
+        computed.add_gate(Gate::LinearCombination {
+            terms: vec![(a, fe(3)), (b, fe(5)), (c, fe(-2))],
+            output: d,
+        });
+        assert_eq!(computed.evaluate().unwrap().variables[d].value, fe(12));
+    }
+
+    #[test]
+    fn assert_equal_compares_a_computed_merkle_root() {
+        let leaves: Vec<FieldElement> = [1001, 2002, 3003, 4004].map(fe).to_vec();
+        let tree = MerkleTree::new(leaves.clone());
+        let merkle_circuit = |claimed_root: FieldElement| {
+            let mut circuit = Circuit::new(Some(Box::new(PoseidonHash::new())));
+            let leaf = circuit.add_input(leaves[1].clone());
+            let path = tree.get_proof(1);
+            let left_sibling = circuit.add_input(path[0].clone());
+            let right_sibling = circuit.add_input(path[1].clone());
+            let (parent, computed_root) = (circuit.alloc_wire(), circuit.alloc_wire());
+            circuit.add_gate(Gate::Hash(left_sibling, leaf, parent));
+            circuit.add_gate(Gate::Hash(parent, right_sibling, computed_root));
+            let root = circuit.add_public_input(claimed_root);
+            circuit.add_gate(Gate::AssertEqual(computed_root, root));
+            circuit
+        };
+
+        assert_eq!(
+            prove_and_verify(&merkle_circuit(tree.get_root()), "assert_equal_root.bin"),
+            (true, Verification::Valid)
+        );
+        let wrong = merkle_circuit(fe(1));
+        let r1cs = wrong.evaluate().unwrap();
+        assert!(r1cs.check(|inputs| wrong.apply_hash_many(inputs)).is_err());
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    /// The `10 + 20 = 30` example from `main.rs`, with the sum public
+    fn addition_circuit() -> Circuit {
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(10));
+        let b = circuit.add_input(fe(20));
+        let sum = circuit.add_public_input(fe(30));
+        circuit.add_gate(Gate::Add(a, b, sum));
+        circuit
+    }
+
+    #[test]
+    fn exported_r1cs_header_follows_the_spec() {
+        let path = temp_path("addition.r1cs");
+        addition_circuit().export_r1cs(&path).unwrap();
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&file[0..4], b"r1cs");
+        assert_eq!(u32_at(&file, 4), 1); // version
+        assert_eq!(u32_at(&file, 8), 3); // header, constraints and wire-to-label sections
+        assert_eq!(u32_at(&file, 12), 1); // header section type
+        assert_eq!(u64_at(&file, 16), 64);
+        assert_eq!(u32_at(&file, 24), 32); // field size
+        assert_eq!(file[28..60], Bn254::modulus().to_bytes_le().1); // the prime
+        assert_eq!(u32_at(&file, 60), 4); // wires, counting the constant one
+        assert_eq!(u32_at(&file, 64), 0); // public outputs
+        assert_eq!(u32_at(&file, 68), 1); // public inputs
+        assert_eq!(u32_at(&file, 72), 2); // private inputs
+        assert_eq!(u64_at(&file, 76), 4); // labels
+        assert_eq!(u32_at(&file, 84), 1); // constraints
+    }
+
+    #[test]
+    fn exported_witness_holds_the_circuit_values() {
+        let path = temp_path("addition.wtns");
+        addition_circuit().export_witness(&path).unwrap();
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&file[0..4], b"wtns");
+        assert_eq!(u32_at(&file, 4), 2); // version
+        assert_eq!(u32_at(&file, 8), 2); // header and witness sections
+        assert_eq!(u32_at(&file, 60), 4); // witness values
+        assert_eq!(u32_at(&file, 64), 2); // witness section type
+        assert_eq!(u64_at(&file, 68), 4 * 32);
+
+        let values: Vec<FieldElement> = file[76..]
+            .chunks_exact(32)
+            .map(|bytes| FieldElement::from_bytes_le(bytes.try_into().unwrap()))
+            .collect();
+        // circom order: the constant one, public inputs, then private inputs
+        assert_eq!(values, [fe(1), fe(30), fe(10), fe(20)]);
+    }
+
+    #[test]
+    fn range_check_accepts_5_and_rejects_300_in_8_bits() {
+        let range_checked = |value: i64| {
+            let mut circuit = Circuit::new(None);
+            let x = circuit.add_input(fe(value));
+            circuit.add_range_check(x, 8);
+            circuit
+        };
+        assert!(holds(&range_checked(5)));
+        assert!(holds(&range_checked(255)));
+        assert!(!holds(&range_checked(256)));
+        assert!(!holds(&range_checked(300)));
+        assert!(!holds(&range_checked(-1)));
+    }
+
+    #[test]
+    fn less_than_compares_two_wires() {
+        for (a, b, expected) in [(3, 5, 1), (5, 3, 0), (4, 4, 0), (0, 255, 1)] {
+            let mut circuit = Circuit::new(None);
+            let a = circuit.add_input(fe(a));
+            let b = circuit.add_input(fe(b));
+            let less = circuit.add_less_than(a, b, 8);
+            assert!(holds(&circuit));
+            assert_eq!(circuit.get_wire(less), Some(fe(expected)));
+        }
+
+        // Claiming 5 < 3 is caught by the constraints
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(5));
+        let b = circuit.add_input(fe(3));
+        let less = circuit.add_less_than(a, b, 8);
+        let claimed = circuit.add_public_input(fe(1));
+        circuit.add_gate(Gate::AssertEqual(less, claimed));
+        assert!(!holds(&circuit));
+    }
+
+    #[test]
+    fn boolean_gates_match_their_truth_tables() {
+        type MakeGate = fn(usize, usize, usize) -> Gate;
+        type Truth = fn(bool, bool) -> bool;
+        let gates: [(MakeGate, Truth); 3] = [
+            (Gate::And, |a, b| a & b),
+            (Gate::Xor, |a, b| a ^ b),
+            (Gate::Or, |a, b| a | b),
+        ];
+        for (make_gate, truth) in gates {
+            for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+                let mut circuit = Circuit::new(None);
+                let a_wire = circuit.add_input(fe(a as i64));
+                let b_wire = circuit.add_input(fe(b as i64));
+                let out = circuit.alloc_wire();
+                circuit.add_gate(make_gate(a_wire, b_wire, out));
+                assert!(holds(&circuit));
+                assert_eq!(circuit.get_wire(out), Some(fe(truth(a, b) as i64)));
+            }
+        }
+    }
+
+    #[test]
+    fn mux_selects_by_a_boolean_selector() {
+        let mux = |selector: i64| {
+            let mut circuit = Circuit::new(None);
+            let selector = circuit.add_input(fe(selector));
+            let if_zero = circuit.add_input(fe(111));
+            let if_one = circuit.add_input(fe(222));
+            let output = circuit.alloc_wire();
+            circuit.add_gate(Gate::Mux {
+                selector,
+                if_zero,
+                if_one,
+                output,
+            });
+            (circuit, output)
+        };
+
+        let (circuit, output) = mux(0);
+        assert!(holds(&circuit));
+        assert_eq!(circuit.get_wire(output), Some(fe(111)));
+        let (circuit, output) = mux(1);
+        assert!(holds(&circuit));
+        assert_eq!(circuit.get_wire(output), Some(fe(222)));
+        let (circuit, _) = mux(2);
+        assert!(!holds(&circuit));
+    }
+
+    #[test]
+    fn gate_with_a_nonexistent_wire_is_described() {
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(1));
+        let b = circuit.add_input(fe(2));
+        circuit.add_gate(Gate::Add(a, b, a));
+        circuit.add_gate(Gate::Mul(a, 7, b));
+
+        let error = circuit.validate().unwrap_err();
+        assert_eq!(
+            error,
+            CircuitError::InvalidWire {
+                gate_index: 1,
+                wire: 7
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "gate #1 references wire 7, which does not exist"
+        );
+        assert!(matches!(
+            circuit.generate_proof(&temp_path("invalid_wire.bin")),
+            Err(ProofError::Circuit(CircuitError::InvalidWire {
+                wire: 7,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn embedded_multiplication_twice_proves() {
+        let mut multiply = Circuit::new(None);
+        let x = multiply.add_input(fe(0));
+        let y = multiply.add_input(fe(0));
+        let product = multiply.alloc_wire();
+        multiply.add_gate(Gate::Mul(x, y, product));
+
+        // (3 * 4) * 5 = 60
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(3));
+        let b = circuit.add_input(fe(4));
+        let c = circuit.add_input(fe(5));
+        let ab = circuit.embed(&multiply, &[a, b])[0];
+        let abc = circuit.embed(&multiply, &[ab, c])[0];
+        let expected = circuit.add_public_input(fe(60));
+        circuit.add_gate(Gate::AssertEqual(abc, expected));
+
+        assert_eq!(circuit.num_gates(), 3);
+        assert_eq!(
+            prove_and_verify(&circuit, "embedded.bin"),
+            (true, Verification::Valid)
+        );
+        assert_eq!(circuit.get_wire(ab), Some(fe(12)));
+    }
+
+    #[test]
+    fn one_template_assigned_two_witnesses() {
+        let circuit = product_circuit(3, 4, 12);
+        let template = circuit.synthesize();
+        let check = |r1cs: &R1CS| r1cs.check(|inputs| circuit.apply_hash_many(inputs));
+
+        let satisfying = template.assign(&[fe(1), fe(3), fe(4), fe(12)]);
+        assert!(check(&satisfying).is_ok());
+        let failing = template.assign(&[fe(1), fe(3), fe(4), fe(13)]);
+        assert!(check(&failing).is_err());
+
+        assert_eq!(satisfying.variables[3].value, fe(12));
+        assert_eq!(failing.variables[3].value, fe(13));
+        assert_eq!(satisfying.constraints.len(), failing.constraints.len());
+    }
+
+    #[test]
+    fn get_wire_reads_a_hash_gate_output() {
+        let mut circuit = Circuit::new(Some(Box::new(PoseidonHash::new())));
+        let a = circuit.add_input(fe(1));
+        let b = circuit.add_input(fe(2));
+        let digest = circuit.alloc_wire();
+        circuit.add_gate(Gate::Hash(a, b, digest));
+
+        assert_eq!(circuit.get_wire(digest), None);
+        let path = temp_path("get_wire.bin");
+        assert!(circuit.generate_proof(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            circuit.get_wire(digest),
+            Some(PoseidonHash::new().hash(&fe(1), &fe(2)))
+        );
+        assert_eq!(circuit.get_wire(a), Some(fe(1)));
+        assert_eq!(circuit.get_wire(99), None);
+    }
+
+    #[test]
+    fn merkle_path_gadget_verifies_leaf_2_of_4() {
+        let leaves: Vec<FieldElement> = [10, 20, 30, 40].map(fe).to_vec();
+        let tree = MerkleTree::new(leaves);
+        let path_circuit = |leaf_value: FieldElement| {
+            let mut circuit = Circuit::new(Some(Box::new(PoseidonHash::new())));
+            let leaf = circuit.add_input(leaf_value);
+            let (mut path, mut bits) = (Vec::new(), Vec::new());
+            for (sibling, is_right) in tree.get_proof_with_directions(2) {
+                path.push(circuit.add_input(sibling));
+                bits.push(circuit.add_input(fe(is_right as i64)));
+            }
+            let root = circuit.add_public_input(tree.get_root());
+            circuit.add_merkle_path_verify(leaf, &path, &bits, root);
+            circuit
+        };
+
+        assert_eq!(
+            prove_and_verify(&path_circuit(fe(30)), "merkle_path.bin"),
+            (true, Verification::Valid)
+        );
+        assert!(!holds(&path_circuit(fe(31))));
+    }
+
+    #[test]
+    fn is_equal_outputs_one_for_equal_inputs() {
+        for (a, b, expected) in [(7, 7, 1), (7, 8, 0), (0, 0, 1), (0, 5, 0)] {
+            let mut circuit = Circuit::new(None);
+            let a = circuit.add_input(fe(a));
+            let b = circuit.add_input(fe(b));
+            let equal = circuit.add_is_equal(a, b);
+            assert!(holds(&circuit));
+            assert_eq!(circuit.get_wire(equal), Some(fe(expected)));
+        }
+    }
+
+    #[test]
+    fn exported_addition_constraint_reads_a_plus_b_times_one_equals_sum() {
+        let path = temp_path("addition_relation.r1cs");
+        addition_circuit().export_r1cs(&path).unwrap();
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(u32_at(&file, 88), 2); // constraints section type
+        let mut offset = 100;
+        let mut read_lc = || {
+            let n_terms = u32_at(&file, offset) as usize;
+            offset += 4;
+            (0..n_terms)
+                .map(|_| {
+                    let wire = u32_at(&file, offset);
+                    let coeff = FieldElement::from_bytes_le(
+                        file[offset + 4..offset + 36].try_into().unwrap(),
+                    );
+                    offset += 36;
+                    (wire, coeff)
+                })
+                .collect::<Vec<_>>()
+        };
+        // circom wires: 0 = one, 1 = sum (public), 2 = a, 3 = b; constraints read A * B - C = 0
+        assert_eq!(read_lc(), [(2, fe(1)), (3, fe(1))]);
+        assert_eq!(read_lc(), [(0, fe(1))]);
+        assert_eq!(read_lc(), [(1, fe(1))]);
+    }
+
+    #[test]
+    fn constant_term_proves_x_plus_5() {
+        let affine = |y: i64| {
+            let mut circuit = Circuit::new(None);
+            let x = circuit.add_input(fe(7));
+            let y = circuit.add_public_input(fe(y));
+            circuit.add_weighted_constraint(
+                &[(x, 1)],
+                &[(Circuit::ONE, 5)],
+                &[(y, 1)],
+                Operation::Add,
+            );
+            circuit
+        };
+        assert_eq!(
+            prove_and_verify(&affine(12), "affine.bin"),
+            (true, Verification::Valid)
+        );
+        assert!(!holds(&affine(13)));
+
+        // Same relation with a gate computing y
+        let mut circuit = Circuit::new(None);
+        let x = circuit.add_input(fe(7));
+        let y = circuit.alloc_wire();
+        circuit.add_gate(Gate::LinearCombination {
+            terms: vec![(x, fe(1)), (Circuit::ONE, fe(5))],
+            output: y,
+        });
+        assert!(holds(&circuit));
+        assert_eq!(circuit.get_wire(y), Some(fe(12)));
+    }
+
+    #[test]
+    fn every_gate_variant_has_a_compact_display() {
+        let cases = [
+            (Gate::Add(1, 2, 3), "Add(1,2)->3"),
+            (Gate::Sub(1, 2, 3), "Sub(1,2)->3"),
+            (Gate::Mul(1, 2, 3), "Mul(1,2)->3"),
+            (Gate::Square(1, 2), "Square(1)->2"),
+            (Gate::And(1, 2, 3), "And(1,2)->3"),
+            (Gate::Xor(1, 2, 3), "Xor(1,2)->3"),
+            (Gate::Or(1, 2, 3), "Or(1,2)->3"),
+            (Gate::Hash(1, 2, 3), "Hash(1,2)->3"),
+            (Gate::Hash3(1, 2, 3, 4), "Hash3(1,2,3)->4"),
+            (
+                Gate::AssertEqualConstant(1, fe(5)),
+                "AssertEqualConstant(1)==5",
+            ),
+            (Gate::AssertEqual(1, 2), "AssertEqual(1,2)"),
+            (Gate::Boolean(1), "Boolean(1)"),
+            (
+                Gate::ConditionalSwap(1, 2, 3, 4, 5),
+                "ConditionalSwap(1,2,3)->(4,5)",
+            ),
+            (
+                Gate::LinearCombination {
+                    terms: vec![(1, fe(3)), (2, fe(-2))],
+                    output: 3,
+                },
+                "LinearCombination(3*1 + -2*2)->3",
+            ),
+            (
+                Gate::Mux {
+                    selector: 1,
+                    if_zero: 2,
+                    if_one: 3,
+                    output: 4,
+                },
+                "Mux(1,2,3)->4",
+            ),
+            (
+                Gate::ToBits {
+                    input: 1,
+                    bits: vec![2, 3],
+                },
+                "ToBits(1)->[2,3]",
+            ),
+            (
+                Gate::IsEqual {
+                    a: 1,
+                    b: 2,
+                    inverse: 3,
+                    not_equal: 4,
+                    output: 5,
+                },
+                "IsEqual(1,2)->5 (hints 3,4)",
+            ),
+            (
+                Gate::Constraint {
+                    left: vec![(1, fe(1))],
+                    right: vec![(2, fe(2))],
+                    output: vec![(3, fe(1))],
+                    operation: Operation::Mul,
+                },
+                "Constraint((1*1) * (2*2))->(1*3)",
+            ),
+            (
+                Gate::NonZero {
+                    wire: 1,
+                    inverse: 2,
+                },
+                "NonZero(1) (hint 2)",
+            ),
+            (Gate::Hash1(1, 2), "Hash1(1)->2"),
+        ];
+        for (gate, expected) in cases {
+            assert_eq!(gate.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn boolean_constraint_accepts_only_0_and_1() {
+        for (value, valid) in [(0, true), (1, true), (2, false), (-1, false)] {
+            let mut circuit = Circuit::new(None);
+            let wire = circuit.add_input(fe(value));
+            circuit.add_boolean(wire);
+            assert_eq!(holds(&circuit), valid, "{}", value);
+        }
+    }
+
+    #[test]
+    fn add_input_from_accepts_any_into_field_element() {
+        let mut circuit = Circuit::new(None);
+        let big = circuit.add_input_from(u64::MAX);
+        let negative = circuit.add_input_from(-7i64);
+        let huge = circuit.add_input_from(Bn254::modulus() + 1);
+        assert_eq!(
+            circuit.get_input(big),
+            Some(&FieldElement::from_u64(u64::MAX))
+        );
+        assert_eq!(circuit.get_input(negative), Some(&fe(-7)));
+        assert_eq!(circuit.get_input(huge), Some(&FieldElement::one()));
+
+        // Values beyond i32 go through gates like any other
+        let sum = circuit.alloc_wire();
+        circuit.add_gate(Gate::Add(big, negative, sum));
+        assert!(holds(&circuit));
+        assert_eq!(
+            circuit.get_wire(sum),
+            Some(FieldElement::from_u64(u64::MAX - 7))
+        );
+    }
+
+    #[test]
+    fn hash3_gate_matches_hash_many() {
+        let inputs = [fe(1), fe(2), fe(3)];
+        let mut circuit = Circuit::new(Some(Box::new(PoseidonHash::new())));
+        let wires: Vec<usize> = inputs
+            .iter()
+            .map(|x| circuit.add_input(x.clone()))
+            .collect();
+        let digest = circuit.alloc_wire();
+        circuit.add_gate(Gate::Hash3(wires[0], wires[1], wires[2], digest));
+
+        assert!(holds(&circuit));
+        let expected = PoseidonHash::new().hash_many(&inputs);
+        assert_eq!(circuit.get_wire(digest), Some(expected.clone()));
+        assert_ne!(expected, PoseidonHash::new().hash(&fe(1), &fe(2)));
+        let stats = circuit.stats().unwrap();
+        assert_eq!((stats.hash3, stats.hash), (1, 0));
+
+        let mut wrong = Circuit::new(Some(Box::new(PoseidonHash::new())));
+        let wires: Vec<usize> = inputs.iter().map(|x| wrong.add_input(x.clone())).collect();
+        let digest = wrong.add_input(expected + fe(1));
+        wrong.add_gate(Gate::Hash3(wires[0], wires[1], wires[2], digest));
+        assert!(!holds(&wrong));
+    }
+
+    #[test]
+    fn cloned_circuit_proves_independently() {
+        let original = product_circuit(3, 4, 12);
+        let mut clone = original.clone();
+        assert!(clone.structurally_eq(&original));
+        assert_eq!(
+            prove_and_verify(&clone, "clone.bin"),
+            (true, Verification::Valid)
+        );
+
+        let extra = clone.add_input(fe(5));
+        clone.add_gate(Gate::Boolean(extra));
+        assert!(!clone.structurally_eq(&original));
+        assert!(!holds(&clone));
+        assert_eq!(original.num_gates(), 1);
+        assert!(holds(&original));
+    }
+
+    #[test]
+    fn preimage_check_accepts_only_the_preimage() {
+        let (secret, salt) = (fe(424242), FieldElement::random_seeded(83));
+        let commitment = PoseidonHash::new().hash(&secret, &salt);
+        let preimage_circuit = |claimed: FieldElement| {
+            let mut circuit = Circuit::new(Some(Box::new(PoseidonHash::new())));
+            let preimage = circuit.add_input(claimed);
+            let salt = circuit.add_input(salt.clone());
+            let commitment = circuit.add_public_input(commitment.clone());
+            circuit.add_preimage_check(preimage, salt, commitment);
+            circuit
+        };
+
+        assert_eq!(
+            prove_and_verify(&preimage_circuit(secret), "preimage.bin"),
+            (true, Verification::Valid)
+        );
+        let (satisfied, verification) =
+            prove_and_verify(&preimage_circuit(fe(1)), "preimage_wrong.bin");
+        assert!(!satisfied);
+        assert!(!verification.is_valid());
+    }
+
+    #[test]
+    fn verifies_a_proof_from_an_in_memory_cursor() {
+        let circuit = product_circuit(3, 4, 12);
+        let path = temp_path("cursor.bin");
+        assert!(circuit.generate_proof(&path).unwrap());
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let verification = circuit
+            .verify_proof_reader(std::io::Cursor::new(bytes), &[fe(12)])
+            .unwrap();
+        assert_eq!(verification, Verification::Valid);
+    }
+
+    #[test]
+    fn pow_fixed_proves_three_to_the_fifth() {
+        let mut circuit = Circuit::new(None);
+        let base = circuit.add_input(fe(3));
+        let claimed = circuit.add_public_input(fe(243));
+        let power = circuit.add_pow_fixed(base, 5);
+        circuit.add_gate(Gate::AssertEqual(power, claimed));
+
+        let r1cs = circuit.evaluate().unwrap();
+        assert_eq!(r1cs.variables[power].value, fe(243));
+        assert_eq!(
+            prove_and_verify(&circuit, "pow_fixed.bin"),
+            (true, Verification::Valid)
+        );
+    }
+
+    #[test]
+    fn declared_output_is_read_back_after_verification() {
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input(fe(10));
+        let b = circuit.add_input(fe(20));
+        let sum = circuit.add_public_input(fe(30));
+        circuit.add_gate(Gate::Add(a, b, sum));
+        circuit.add_output(sum);
+
+        let path = temp_path("outputs.bin");
+        assert!(circuit.generate_proof(&path).unwrap());
+        let outputs = circuit.verify_and_get_outputs(&path, &[fe(30)]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(outputs, [fe(30)]);
+    }
+
+    #[test]
+    fn permutation_check_accepts_reorderings_only() {
+        let permutation_circuit = |b_values: [i64; 3]| {
+            let mut circuit = Circuit::new(Some(Box::new(PoseidonHash::new())));
+            let a: Vec<usize> = [1, 2, 3].map(|v| circuit.add_input(fe(v))).to_vec();
+            let b: Vec<usize> = b_values.map(|v| circuit.add_input(fe(v))).to_vec();
+            circuit.add_permutation_check(&a, &b);
+            circuit
+        };
+
+        assert!(holds(&permutation_circuit([3, 1, 2])));
+        assert!(!holds(&permutation_circuit([1, 2, 4])));
+    }
+
+    #[test]
+    fn weighted_constraint_proves_a_minus_two_b() {
+        let weighted_circuit = |c: i64| {
+            let mut circuit = Circuit::new(None);
+            let a = circuit.add_input(fe(10));
+            let b = circuit.add_input(fe(3));
+            let c = circuit.add_public_input(fe(c));
+            circuit.add_weighted_constraint(&[(a, 1), (b, -2)], &[], &[(c, 1)], Operation::Add);
+            circuit
+        };
+
+        assert_eq!(
+            prove_and_verify(&weighted_circuit(4), "weighted.bin"),
+            (true, Verification::Valid)
+        );
+        assert!(!holds(&weighted_circuit(5)));
+    }
+
+    #[test]
+    fn nonzero_rejects_a_zero_wire() {
+        let nonzero_circuit = |value: i64| {
+            let mut circuit = Circuit::new(None);
+            let wire = circuit.add_input(fe(value));
+            circuit.add_nonzero(wire);
+            circuit
+        };
+
+        assert_eq!(
+            prove_and_verify(&nonzero_circuit(7), "nonzero.bin"),
+            (true, Verification::Valid)
+        );
+
+        let circuit = nonzero_circuit(0);
+        let error = circuit
+            .evaluate()
+            .unwrap()
+            .check(|inputs| circuit.apply_hash_many(inputs))
+            .unwrap_err();
+        assert!(matches!(error, ConstraintError::Unsatisfied(_)));
+        assert_eq!(error.index(), Some(0));
+        assert_eq!(
+            error.to_string(),
+            "Constraint #0: Multiplication constraint not satisfied: \
+             left_value * right_value = 0, while output_value = 1"
+        );
+    }
+
+    #[test]
+    fn conditional_equal_only_binds_when_the_flag_is_set() {
+        let conditional_circuit = |flag: i64, a: i64, b: i64| {
+            let mut circuit = Circuit::new(None);
+            let flag = circuit.add_input(fe(flag));
+            let a = circuit.add_input(fe(a));
+            let b = circuit.add_input(fe(b));
+            circuit.add_conditional_equal(flag, a, b);
+            circuit
+        };
 
-    /// Verifies the proof by deserializing it from a binary file and checking if it's valid
-    pub fn verify_proof(&self, proof_file: &str) -> bool {
-        let proof_data = std::fs::read(proof_file).expect("Failed to read proof file");
-        let is_valid = bincode::deserialize(&proof_data).expect("Failed to deserialize proof");
-        is_valid
+        assert!(!holds(&conditional_circuit(1, 4, 5)));
+        assert!(holds(&conditional_circuit(0, 4, 5)));
+        assert!(holds(&conditional_circuit(1, 4, 4)));
     }
 }
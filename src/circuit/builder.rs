@@ -0,0 +1,125 @@
+use super::{Circuit, Gate};
+use crate::field::FieldElement;
+use crate::hash_functions::HashFunction;
+
+/// Handle to a wire created by a `CircuitBuilder`.
+/// Only the builder hands these out, so a handle always refers to a wire that exists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Wire(usize);
+
+impl Wire {
+    /// Index of the wire in the built `Circuit`
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Builds a `Circuit` one operation at a time.
+/// Every operation allocates its output wire and returns its handle (`let sum = builder.add(a, b);`),
+/// so there is no index bookkeeping.
+pub struct CircuitBuilder {
+    circuit: Circuit,
+}
+
+impl CircuitBuilder {
+    pub fn new(hash_function: Option<Box<dyn HashFunction>>) -> Self {
+        Self {
+            circuit: Circuit::new(hash_function),
+        }
+    }
+
+    /// The constant one wire, for constant terms
+    pub fn one(&self) -> Wire {
+        Wire(Circuit::ONE)
+    }
+
+    /// Adds a private witness value
+    pub fn input(&mut self, value: FieldElement) -> Wire {
+        Wire(self.circuit.add_input(value))
+    }
+
+    /// Adds a value the verifier also knows
+    pub fn public_input(&mut self, value: FieldElement) -> Wire {
+        Wire(self.circuit.add_public_input(value))
+    }
+
+    pub fn add(&mut self, a: Wire, b: Wire) -> Wire {
+        self.binary_gate(a, b, Gate::Add)
+    }
+
+    /// `a - b`
+    pub fn sub(&mut self, a: Wire, b: Wire) -> Wire {
+        self.binary_gate(a, b, Gate::Sub)
+    }
+
+    pub fn mul(&mut self, a: Wire, b: Wire) -> Wire {
+        self.binary_gate(a, b, Gate::Mul)
+    }
+
+    pub fn square(&mut self, a: Wire) -> Wire {
+        let output = self.circuit.alloc_wire();
+        self.circuit.add_gate(Gate::Square(a.0, output));
+        Wire(output)
+    }
+
+    /// Two-to-one hash with the circuit's hash function
+    pub fn hash(&mut self, a: Wire, b: Wire) -> Wire {
+        self.binary_gate(a, b, Gate::Hash)
+    }
+
+    pub fn assert_equal(&mut self, a: Wire, b: Wire) -> &mut Self {
+        self.circuit.add_gate(Gate::AssertEqual(a.0, b.0));
+        self
+    }
+
+    pub fn assert_equal_constant(&mut self, wire: Wire, constant: FieldElement) -> &mut Self {
+        self.circuit
+            .add_gate(Gate::AssertEqualConstant(wire.0, constant));
+        self
+    }
+
+    /// Finishes construction. The circuit can still be extended through its own API.
+    pub fn build(self) -> Circuit {
+        self.circuit
+    }
+
+    fn binary_gate(&mut self, a: Wire, b: Wire, gate: fn(usize, usize, usize) -> Gate) -> Wire {
+        let output = self.circuit.alloc_wire();
+        self.circuit.add_gate(gate(a.0, b.0, output));
+        Wire(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proof::Verification;
+
+    #[test]
+    fn builds_and_proves_10_plus_20() {
+        let mut builder = CircuitBuilder::new(None);
+        let a = builder.input(FieldElement::from_i32(10));
+        let b = builder.input(FieldElement::from_i32(20));
+        let expected = builder.public_input(FieldElement::from_i32(30));
+        let sum = builder.add(a, b);
+        builder.assert_equal(sum, expected);
+        let circuit = builder.build();
+
+        let path = std::env::temp_dir()
+            .join(format!("mini_zk_{}_builder.bin", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        assert!(circuit.generate_proof(&path).unwrap());
+        assert_eq!(
+            circuit
+                .verify_proof(&path, &[FieldElement::from_i32(30)])
+                .unwrap(),
+            Verification::Valid
+        );
+        assert_eq!(
+            circuit.get_wire(sum.index()),
+            Some(FieldElement::from_i32(30))
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,44 +1,462 @@
-use num_bigint::{BigInt, ToBigInt};
-use num_traits::Zero;
-use serde::{Deserialize, Serialize};
+use num_bigint::{BigInt, Sign, ToBigInt};
+use num_traits::{One, Zero};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
 use std::fmt;
-use std::ops::{Add, Mul, Sub};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use subtle::ConstantTimeEq;
+
+mod fr; // `to_fr` / `from_fr`, so `ff_ce` stays out of the rest of the crate
 
 // BN128 scalar field size
-const MODULUS_STR: &str =
+const BN254_MODULUS_STR: &str =
     "21888242871839275222246405745257275088548364400416034343698204186575808495617";
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub struct FieldElement {
+// BLS12-381 scalar field size
+const BLS12_381_MODULUS_STR: &str =
+    "52435875175126190479447740508185965837690552500527637822603658699938581184513";
+
+/// Selects the prime field a `FieldElement` lives in.
+///
+/// Implementors are zero-sized markers; the modulus is the only thing that varies between curves.
+/// The modulus must be an odd prime below 2^256 so elements fit the 32-byte encoding.
+pub trait FieldParams: Clone + fmt::Debug + PartialEq + Eq + Send + Sync + 'static {
+    fn modulus() -> BigInt;
+}
+
+/// Scalar field of BN254 (a.k.a. alt_bn128 / BN128), the curve used by circom and poseidon-rs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bn254;
+
+impl FieldParams for Bn254 {
+    fn modulus() -> BigInt {
+        BigInt::parse_bytes(BN254_MODULUS_STR.as_bytes(), 10).expect("Invalid modulus string")
+    }
+}
+
+/// Scalar field of BLS12-381
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bls12_381;
+
+impl FieldParams for Bls12_381 {
+    fn modulus() -> BigInt {
+        BigInt::parse_bytes(BLS12_381_MODULUS_STR.as_bytes(), 10).expect("Invalid modulus string")
+    }
+}
+
+/// Returned when a string cannot be parsed into a `FieldElement`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    InvalidDigits { input: String, radix: u32 },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "cannot parse a field element from an empty string"),
+            ParseError::InvalidDigits { input, radix } => {
+                write!(f, "'{}' is not a valid base-{} field element", input, radix)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// An element of the prime field selected by `P`. Most code wants the BN254 field and spells it
+/// `FieldElement`; `Fp<Bls12_381>` and other fields are the same type with another modulus.
+///
+/// Serializes as a decimal string in human-readable formats (JSON) and as the raw `BigInt` in
+/// binary formats (bincode), which keeps proof files compact.
+///
+/// `==` compares the `BigInt`s and can return at the first differing limb, so its running time
+/// depends on the values. Compare secrets with `ct_eq`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fp<P: FieldParams> {
     pub value: BigInt,
+    params: PhantomData<P>,
 }
 
-impl FieldElement {
+/// An element of the BN254 scalar field. A plain alias rather than a default type parameter, so
+/// `FieldElement::from_i32(1)` needs no type annotation to pick the field.
+pub type FieldElement = Fp<Bn254>;
+pub type Bn254Field = Fp<Bn254>;
+pub type Bls12_381Field = Fp<Bls12_381>;
+
+impl<P: FieldParams> Fp<P> {
     pub fn new(value: BigInt) -> Self {
         let modulus = Self::get_modulus();
         let mut v = value % &modulus;
         if v < BigInt::zero() {
             v += &modulus;
         }
-        FieldElement { value: v }
+        Self::from_reduced(v)
+    }
+
+    /// Wraps a value the caller already knows is in `[0, P)`
+    fn from_reduced(value: BigInt) -> Self {
+        Fp {
+            value,
+            params: PhantomData,
+        }
+    }
+
+    /// Brings `value` back into `[0, P)` after an in-place operation
+    fn reduce(&mut self) {
+        let modulus = Self::get_modulus();
+        self.value %= &modulus;
+        if self.value < BigInt::zero() {
+            self.value += &modulus;
+        }
     }
 
     pub fn get_modulus() -> BigInt {
-        BigInt::parse_bytes(MODULUS_STR.as_bytes(), 10).expect("Invalid modulus string")
+        P::modulus()
     }
 
     pub fn from_i32(v: i32) -> Self {
         Self::new(v.to_bigint().unwrap())
     }
+
+    pub fn from_u64(v: u64) -> Self {
+        Self::new(BigInt::from(v))
+    }
+
+    /// Negative values wrap around to `P - |v|`, like `from_i32`
+    pub fn from_i64(v: i64) -> Self {
+        Self::new(BigInt::from(v))
+    }
+
+    /// The value as a `u64`, or `None` if it is `2^64` or more
+    pub fn try_to_u64(&self) -> Option<u64> {
+        u64::try_from(&self.value).ok()
+    }
+
+    /// The value as an `i64`, reading `P - n` as `-n` (the inverse of `from_i64`).
+    /// `None` if neither representative fits.
+    pub fn try_to_i64(&self) -> Option<i64> {
+        i64::try_from(&self.value)
+            .or_else(|_| i64::try_from(&self.value - Self::get_modulus()))
+            .ok()
+    }
+
+    /// Legendre symbol via Euler's criterion: `0` for zero, `1` for quadratic residues, `-1` otherwise
+    pub fn legendre(&self) -> i32 {
+        if self.is_zero() {
+            return 0;
+        }
+        let euler_exp = (Self::get_modulus() - BigInt::one()) >> 1;
+        if self.pow(&euler_exp).is_one() { 1 } else { -1 }
+    }
+
+    /// True if the element has a square root in the field (zero counts as a residue)
+    pub fn is_quadratic_residue(&self) -> bool {
+        self.legendre() != -1
+    }
+
+    /// Returns a square root of the element, or `None` if it is not a quadratic residue.
+    ///
+    /// Uses Tonelli–Shanks: the BN128 prime is `1 mod 4` (P - 1 = 2^28 * q), so the
+    /// `(P + 1) / 4` shortcut doesn't apply. The general algorithm works for any odd `P`.
+    /// Which of the two roots is returned is unspecified.
+    pub fn sqrt(&self) -> Option<Self> {
+        match self.legendre() {
+            0 => return Some(Self::zero()),
+            -1 => return None,
+            _ => {}
+        }
+
+        let p_minus_one = Self::get_modulus() - BigInt::one();
+
+        // Factor P - 1 = q * 2^s with q odd
+        let s = p_minus_one.trailing_zeros().expect("P - 1 is non-zero");
+        let q = &p_minus_one >> s;
+
+        // Any quadratic non-residue works as z; the smallest one is found quickly
+        let mut z = Self::from_i32(2);
+        while z.legendre() != -1 {
+            z += Self::one();
+        }
+
+        let mut m = s;
+        let mut c = z.pow(&q);
+        let mut t = self.pow(&q);
+        let mut r = self.pow(&((&q + BigInt::one()) >> 1));
+
+        while !t.is_one() {
+            // Find the least i in (0, m) with t^(2^i) == 1
+            let mut i = 0;
+            let mut t_pow = t.clone();
+            while !t_pow.is_one() {
+                t_pow = &t_pow * &t_pow;
+                i += 1;
+            }
+
+            let mut b = c;
+            for _ in 0..(m - i - 1) {
+                b = &b * &b;
+            }
+
+            m = i;
+            c = &b * &b;
+            t = &t * &c;
+            r = &r * &b;
+        }
+
+        Some(r)
+    }
+
+    /// Parses a hexadecimal string (optionally `0x`-prefixed) and reduces it mod P
+    pub fn from_hex(s: &str) -> Result<Self, ParseError> {
+        let digits = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s);
+        Self::parse_radix(s, digits, 16)
+    }
+
+    /// Parses a base-10 string and reduces it mod P
+    pub fn from_dec_str(s: &str) -> Result<Self, ParseError> {
+        Self::parse_radix(s, s, 10)
+    }
+
+    /// Lowercase, `0x`-prefixed hex encoding of the canonical value
+    pub fn to_hex(&self) -> String {
+        format!("0x{}", self.value.to_str_radix(16))
+    }
+
+    /// Canonical 32-byte little-endian encoding of the reduced value.
+    /// `value` is public and may have been set outside `[0, P)` by hand, so it is reduced first.
+    pub fn to_bytes_le(&self) -> [u8; 32] {
+        let reduced = Self::new(self.value.clone());
+        let (_, bytes) = reduced.value.to_bytes_le(); // Reduced into [0, P), so never negative
+        let mut out = [0u8; 32];
+        out[..bytes.len()].copy_from_slice(&bytes);
+        out
+    }
+
+    /// Decodes a 32-byte little-endian integer, reducing it mod P if it exceeds the modulus
+    pub fn from_bytes_le(bytes: &[u8; 32]) -> Self {
+        Self::new(BigInt::from_bytes_le(Sign::Plus, bytes))
+    }
+
+    fn parse_radix(input: &str, digits: &str, radix: u32) -> Result<Self, ParseError> {
+        if digits.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        BigInt::parse_bytes(digits.as_bytes(), radix)
+            .map(Self::new)
+            .ok_or_else(|| ParseError::InvalidDigits {
+                input: input.to_string(),
+                radix,
+            })
+    }
+
+    /// Samples a uniformly random element.
+    ///
+    /// Draws random bytes for the bit length of P (254 bits for BN128), clears the bits above it and
+    /// rejects anything `>= P`, so there is no modulo bias. Roughly 3 in 4 BN128 draws are accepted.
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        let modulus = Self::get_modulus();
+        let bits = modulus.bits() as usize;
+        loop {
+            let mut bytes = [0u8; 32];
+            rng.fill_bytes(&mut bytes[..bits.div_ceil(8)]);
+            if !bits.is_multiple_of(8) {
+                bytes[bits / 8] &= 0xff >> (8 - bits % 8);
+            }
+
+            let candidate = BigInt::from_bytes_le(Sign::Plus, &bytes);
+            if candidate < modulus {
+                return Self::from_reduced(candidate);
+            }
+        }
+    }
+
+    /// Deterministic variant of `random`, handy for reproducible tests and fixtures
+    pub fn random_seeded(seed: u64) -> Self {
+        Self::random(&mut StdRng::seed_from_u64(seed))
+    }
+
+    /// The additive identity. Already reduced, so it skips the modulus round-trip in `new`.
+    pub fn zero() -> Self {
+        Self::from_reduced(BigInt::zero())
+    }
+
+    /// The multiplicative identity
+    pub fn one() -> Self {
+        Self::from_reduced(BigInt::one())
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.value.is_zero()
+    }
+
+    pub fn is_one(&self) -> bool {
+        self.value.is_one()
+    }
+
+    /// Equality whose comparison step takes the same time for any two values: the 32-byte
+    /// canonical encodings are compared with `subtle::ConstantTimeEq`, which never stops at the
+    /// first difference. Producing those encodings goes through `BigInt` and is not itself
+    /// constant-time.
+    pub fn ct_eq(&self, other: &Self) -> bool {
+        self.to_bytes_le().ct_eq(&other.to_bytes_le()).into()
+    }
+
+    /// `self * k mod P` for a raw integer `k`, which may be negative or larger than P.
+    /// Same result as `self * FieldElement::new(k)` without reducing `k` first.
+    pub fn scale(&self, k: &BigInt) -> Self {
+        Self::new(&self.value * k)
+    }
+
+    /// `self + k mod P` for a raw integer `k`, which may be negative or larger than P
+    pub fn add_scalar(&self, k: &BigInt) -> Self {
+        Self::new(&self.value + k)
+    }
+
+    /// Raises the element to `exp` using square-and-multiply.
+    /// Every intermediate product is reduced mod P, so large exponents never blow up the `BigInt`.
+    pub fn pow(&self, exp: &BigInt) -> Self {
+        assert!(exp.sign() != Sign::Minus, "Exponent must be non-negative");
+
+        let mut result = Self::one();
+        for i in (0..exp.bits()).rev() {
+            result = &result * &result;
+            if exp.bit(i) {
+                result = &result * self;
+            }
+        }
+        result
+    }
+
+    /// Convenience wrapper around `pow` for small, fixed exponents
+    pub fn pow_u64(&self, exp: u64) -> Self {
+        self.pow(&BigInt::from(exp))
+    }
+
+    /// Multiplicative inverse via Fermat's little theorem (`self^(P-2)`). Zero has none.
+    pub fn inverse(&self) -> Option<Self> {
+        if self.is_zero() {
+            return None;
+        }
+        Some(self.pow(&(Self::get_modulus() - BigInt::from(2))))
+    }
+
+    /// Inverts every element with a single field inversion (Montgomery's trick).
+    /// Zeros have no inverse and are left as zero in the output.
+    pub fn batch_inverse(elements: &[Self]) -> Vec<Self> {
+        // prefix[i] = product of the nonzero elements before index i
+        let mut prefix = Vec::with_capacity(elements.len());
+        let mut acc = Self::one();
+        for element in elements {
+            prefix.push(acc.clone());
+            if !element.is_zero() {
+                acc *= element;
+            }
+        }
+
+        let mut acc_inverse = acc
+            .inverse()
+            .expect("product of nonzero elements is nonzero");
+        let mut result = vec![Self::zero(); elements.len()];
+        for (i, element) in elements.iter().enumerate().rev() {
+            if element.is_zero() {
+                continue;
+            }
+            // acc_inverse = 1 / (prefix[i] * element), so this cancels everything but the element
+            result[i] = &acc_inverse * &prefix[i];
+            acc_inverse *= element;
+        }
+        result
+    }
+}
+
+/// Binary layout of a `FieldElement`: a struct with a single `value` field
+#[derive(Serialize, Deserialize)]
+struct BinaryRepr {
+    value: BigInt,
+}
+
+impl<P: FieldParams> Serialize for Fp<P> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.value.to_string())
+        } else {
+            BinaryRepr {
+                value: self.value.clone(),
+            }
+            .serialize(serializer)
+        }
+    }
 }
 
-impl fmt::Display for FieldElement {
+impl<'de, P: FieldParams> Deserialize<'de> for Fp<P> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let decimal = String::deserialize(deserializer)?;
+            Self::from_dec_str(&decimal).map_err(serde::de::Error::custom)
+        } else {
+            let repr = BinaryRepr::deserialize(deserializer)?;
+            Ok(Self::new(repr.value))
+        }
+    }
+}
+
+impl<P: FieldParams> fmt::Display for Fp<P> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.value)
     }
 }
 
-impl Add for FieldElement {
+/// Orders elements by their canonical representative in `[0, P)`.
+/// This is only for sorting and deduplication; a prime field has no order compatible with its arithmetic.
+impl<P: FieldParams> Ord for Fp<P> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<P: FieldParams> PartialOrd for Fp<P> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Hashes the canonical little-endian encoding, so equal elements always hash alike
+impl<P: FieldParams> Hash for Fp<P> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_bytes_le().hash(state);
+    }
+}
+
+/// Reduces mod P, like `from_u64`
+impl<P: FieldParams> From<u64> for Fp<P> {
+    fn from(v: u64) -> Self {
+        Self::from_u64(v)
+    }
+}
+
+/// Negative values wrap to `P - |v|`, like `from_i64`
+impl<P: FieldParams> From<i64> for Fp<P> {
+    fn from(v: i64) -> Self {
+        Self::from_i64(v)
+    }
+}
+
+/// Reduces mod P, like `new`
+impl<P: FieldParams> From<BigInt> for Fp<P> {
+    fn from(v: BigInt) -> Self {
+        Self::new(v)
+    }
+}
+
+impl<P: FieldParams> Add for Fp<P> {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
@@ -46,15 +464,15 @@ impl Add for FieldElement {
     }
 }
 
-impl Add<&FieldElement> for &FieldElement {
-    type Output = FieldElement;
+impl<P: FieldParams> Add<&Fp<P>> for &Fp<P> {
+    type Output = Fp<P>;
 
-    fn add(self, other: &FieldElement) -> FieldElement {
-        FieldElement::new(&self.value + &other.value)
+    fn add(self, other: &Fp<P>) -> Fp<P> {
+        Fp::new(&self.value + &other.value)
     }
 }
 
-impl Sub for FieldElement {
+impl<P: FieldParams> Sub for Fp<P> {
     type Output = Self;
 
     fn sub(self, other: Self) -> Self {
@@ -62,7 +480,7 @@ impl Sub for FieldElement {
     }
 }
 
-impl Mul for FieldElement {
+impl<P: FieldParams> Mul for Fp<P> {
     type Output = Self;
 
     fn mul(self, other: Self) -> Self {
@@ -70,10 +488,462 @@ impl Mul for FieldElement {
     }
 }
 
-impl Mul<&FieldElement> for &FieldElement {
-    type Output = FieldElement;
+impl<P: FieldParams> Mul<&Fp<P>> for &Fp<P> {
+    type Output = Fp<P>;
+
+    fn mul(self, other: &Fp<P>) -> Fp<P> {
+        Fp::new(&self.value * &other.value)
+    }
+}
+
+impl<P: FieldParams> AddAssign<&Fp<P>> for Fp<P> {
+    fn add_assign(&mut self, other: &Fp<P>) {
+        self.value += &other.value;
+        self.reduce();
+    }
+}
+
+impl<P: FieldParams> AddAssign for Fp<P> {
+    fn add_assign(&mut self, other: Self) {
+        *self += &other;
+    }
+}
+
+impl<P: FieldParams> SubAssign<&Fp<P>> for Fp<P> {
+    fn sub_assign(&mut self, other: &Fp<P>) {
+        self.value -= &other.value;
+        self.reduce();
+    }
+}
+
+impl<P: FieldParams> SubAssign for Fp<P> {
+    fn sub_assign(&mut self, other: Self) {
+        *self -= &other;
+    }
+}
+
+impl<P: FieldParams> MulAssign<&Fp<P>> for Fp<P> {
+    fn mul_assign(&mut self, other: &Fp<P>) {
+        self.value *= &other.value;
+        self.reduce();
+    }
+}
+
+impl<P: FieldParams> MulAssign for Fp<P> {
+    fn mul_assign(&mut self, other: Self) {
+        *self *= &other;
+    }
+}
+
+impl<P: FieldParams> Neg for Fp<P> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        -&self
+    }
+}
+
+impl<P: FieldParams> Neg for &Fp<P> {
+    type Output = Fp<P>;
+
+    fn neg(self) -> Fp<P> {
+        // `new` folds `modulus - 0` back to zero
+        Fp::new(P::modulus() - &self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 7-bit field, small enough that `random` has to clear whole bytes above the modulus
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct F97;
+
+    impl FieldParams for F97 {
+        fn modulus() -> BigInt {
+            BigInt::from(97)
+        }
+    }
+
+    #[test]
+    fn plain_field_element_needs_no_type_annotation() {
+        let one = FieldElement::from_i32(1);
+        assert!(one.is_one());
+    }
+
+    #[test]
+    fn same_value_reduces_differently_under_two_moduli() {
+        let bn = Bn254Field::from_i32(-1);
+        let bls = Bls12_381Field::from_i32(-1);
+        assert_eq!(bn.value, Bn254::modulus() - 1);
+        assert_eq!(bls.value, Bls12_381::modulus() - 1);
+        assert_ne!(bn.value, bls.value);
+        assert_eq!(Fp::<F97>::from_i32(100).value, BigInt::from(3));
+    }
+
+    #[test]
+    fn random_works_for_small_moduli() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..200 {
+            let element = Fp::<F97>::random(&mut rng);
+            assert!(element.value < BigInt::from(97));
+        }
+    }
+
+    #[test]
+    fn hand_set_values_are_reduced_before_encoding() {
+        let mut oversized = FieldElement::zero();
+        oversized.value = Bn254::modulus() * 5 + 3;
+        let mut negative = FieldElement::zero();
+        negative.value = BigInt::from(-1);
 
-    fn mul(self, other: &FieldElement) -> FieldElement {
-        FieldElement::new(&self.value * &other.value)
+        assert_eq!(
+            oversized.to_bytes_le(),
+            FieldElement::from_i32(3).to_bytes_le()
+        );
+        assert_eq!(
+            negative.to_bytes_le(),
+            FieldElement::from_i32(-1).to_bytes_le()
+        );
+
+        let hash = |element: &FieldElement| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            element.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&oversized), hash(&FieldElement::from_i32(3)));
+    }
+
+    #[test]
+    fn ct_eq_agrees_with_eq() {
+        let values = [
+            FieldElement::zero(),
+            FieldElement::one(),
+            FieldElement::from_i32(-1),
+            FieldElement::from_u64(u64::MAX),
+            FieldElement::random_seeded(1),
+            FieldElement::random_seeded(2),
+        ];
+        for a in &values {
+            for b in &values {
+                assert_eq!(a.ct_eq(b), a == b, "{} vs {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn pow_matches_repeated_multiplication() {
+        for base in [0, 1, 2, 7, -3] {
+            let x = FieldElement::from_i32(base);
+            let mut expected = FieldElement::one();
+            for exp in 0..20u64 {
+                assert_eq!(x.pow_u64(exp), expected, "{}^{}", base, exp);
+                expected *= &x;
+            }
+        }
+    }
+
+    #[test]
+    fn negation_is_the_additive_inverse() {
+        for value in [0, 1, 2, 12345, -7, i32::MAX] {
+            let x = FieldElement::from_i32(value);
+            assert_eq!(x.clone() + (-x.clone()), FieldElement::from_i32(0));
+            assert_eq!(&x + &(-&x), FieldElement::zero());
+        }
+        assert_eq!(-FieldElement::zero(), FieldElement::zero());
+        assert_eq!(-FieldElement::one(), FieldElement::from_i32(-1));
+    }
+
+    #[test]
+    fn zero_and_one_survive_reduction() {
+        assert!(FieldElement::zero().is_zero());
+        assert!(FieldElement::one().is_one());
+        assert!(!FieldElement::zero().is_one());
+        assert!(!FieldElement::one().is_zero());
+
+        let modulus = FieldElement::get_modulus();
+        assert!(FieldElement::new(modulus.clone()).is_zero());
+        assert!(FieldElement::new(modulus + 1).is_one());
+        assert_eq!(FieldElement::zero(), FieldElement::from_i32(0));
+        assert_eq!(FieldElement::one(), FieldElement::from_i32(1));
+        assert!((FieldElement::from_i32(-1) + FieldElement::one()).is_zero());
+    }
+
+    #[test]
+    fn hex_and_decimal_strings_round_trip() {
+        for x in [
+            FieldElement::zero(),
+            FieldElement::from_i32(255),
+            FieldElement::from_i32(-1),
+            FieldElement::random_seeded(3),
+        ] {
+            assert_eq!(FieldElement::from_hex(&x.to_hex()).unwrap(), x);
+            assert_eq!(FieldElement::from_dec_str(&x.to_string()).unwrap(), x);
+        }
+        assert_eq!(FieldElement::from_i32(255).to_hex(), "0xff");
+        assert_eq!(
+            FieldElement::from_hex("FF").unwrap(),
+            FieldElement::from_i32(255)
+        );
+        assert_eq!(
+            FieldElement::from_dec_str(BN254_MODULUS_STR).unwrap(),
+            FieldElement::zero()
+        );
+    }
+
+    #[test]
+    fn malformed_strings_are_parse_errors() {
+        assert_eq!(FieldElement::from_hex("0x"), Err(ParseError::Empty));
+        assert_eq!(FieldElement::from_dec_str(""), Err(ParseError::Empty));
+        assert_eq!(
+            FieldElement::from_hex("0xzz"),
+            Err(ParseError::InvalidDigits {
+                input: "0xzz".to_string(),
+                radix: 16
+            })
+        );
+        assert!(FieldElement::from_dec_str("12a").is_err());
+    }
+
+    #[test]
+    fn bytes_round_trip_and_reduce_above_the_modulus() {
+        for x in [
+            FieldElement::zero(),
+            FieldElement::one(),
+            FieldElement::from_i32(-1),
+            FieldElement::random_seeded(4),
+        ] {
+            assert_eq!(FieldElement::from_bytes_le(&x.to_bytes_le()), x);
+        }
+
+        let mut bytes = [0u8; 32];
+        let (_, modulus) = (FieldElement::get_modulus() + BigInt::from(5)).to_bytes_le();
+        bytes[..modulus.len()].copy_from_slice(&modulus);
+        assert_eq!(
+            FieldElement::from_bytes_le(&bytes),
+            FieldElement::from_i32(5)
+        );
+        assert_eq!(
+            FieldElement::from_bytes_le(&[0xff; 32]).to_bytes_le(),
+            FieldElement::new(BigInt::from_bytes_le(Sign::Plus, &[0xff; 32])).to_bytes_le()
+        );
+    }
+
+    #[test]
+    fn sqrt_of_a_square_is_plus_or_minus_the_root() {
+        for seed in 0..10 {
+            let x = FieldElement::random_seeded(seed);
+            let root = (&x * &x).sqrt().unwrap();
+            assert!(root == x || root == -x.clone());
+        }
+        assert_eq!(FieldElement::zero().sqrt(), Some(FieldElement::zero()));
+        // 5 is the smallest quadratic non-residue modulo the BN128 scalar prime
+        assert_eq!(FieldElement::from_i32(5).sqrt(), None);
+        assert_eq!(Fp::<F97>::from_i32(5).sqrt(), None);
+    }
+
+    #[test]
+    fn legendre_of_small_residues_and_non_residues() {
+        // Modulo 97: 1, 2, 3, 4 are squares (2 = 14^2, 3 = 10^2), 5 and 7 are not
+        for residue in [1, 2, 3, 4, 96] {
+            assert_eq!(Fp::<F97>::from_i32(residue).legendre(), 1, "{}", residue);
+        }
+        for non_residue in [5, 7] {
+            assert_eq!(Fp::<F97>::from_i32(non_residue).legendre(), -1);
+            assert!(!Fp::<F97>::from_i32(non_residue).is_quadratic_residue());
+        }
+        assert_eq!(Fp::<F97>::zero().legendre(), 0);
+        assert!(Fp::<F97>::zero().is_quadratic_residue());
+
+        assert_eq!(FieldElement::from_i32(4).legendre(), 1);
+        assert_eq!(FieldElement::from_i32(5).legendre(), -1);
+        assert!(
+            FieldElement::random_seeded(5)
+                .pow_u64(2)
+                .is_quadratic_residue()
+        );
+    }
+
+    #[test]
+    fn assign_operators_match_the_by_value_ones() {
+        let pairs = [(3, 4), (-1, 2), (0, -5), (i32::MAX, i32::MAX)];
+        for (a, b) in pairs {
+            let (a, b) = (FieldElement::from_i32(a), FieldElement::from_i32(b));
+
+            let mut sum = a.clone();
+            sum += &b;
+            assert_eq!(sum, a.clone() + b.clone());
+            let mut difference = a.clone();
+            difference -= b.clone();
+            assert_eq!(difference, a.clone() - b.clone());
+            let mut product = a.clone();
+            product *= &b;
+            assert_eq!(product, a.clone() * b.clone());
+        }
+    }
+
+    #[test]
+    fn random_values_are_in_range_and_vary() {
+        let mut rng = StdRng::seed_from_u64(12);
+        let samples: Vec<FieldElement> = (0..100).map(|_| FieldElement::random(&mut rng)).collect();
+        assert!(samples.iter().all(|x| x.value < Bn254::modulus()));
+        let distinct: std::collections::HashSet<_> = samples.iter().collect();
+        assert_eq!(distinct.len(), samples.len());
+        // A uniform sample of 100 has its top bit (bit 253) set in roughly a third of the draws
+        let high = samples.iter().filter(|x| x.value.bit(253)).count();
+        assert!(
+            (10..60).contains(&high),
+            "{} of 100 have the top bit set",
+            high
+        );
+
+        assert_eq!(
+            FieldElement::random_seeded(9),
+            FieldElement::random_seeded(9)
+        );
+        assert_ne!(
+            FieldElement::random_seeded(9),
+            FieldElement::random_seeded(10)
+        );
+    }
+
+    #[test]
+    fn batch_inverse_matches_individual_inverses() {
+        let elements = [
+            FieldElement::from_i32(3),
+            FieldElement::zero(),
+            FieldElement::from_i32(-1),
+            FieldElement::random_seeded(40),
+            FieldElement::one(),
+        ];
+        let inverses = FieldElement::batch_inverse(&elements);
+        assert_eq!(inverses.len(), elements.len());
+        for (element, inverse) in elements.iter().zip(&inverses) {
+            assert_eq!(
+                *inverse,
+                element.inverse().unwrap_or_else(FieldElement::zero)
+            );
+        }
+        assert!(FieldElement::batch_inverse(&[]).is_empty());
+    }
+
+    #[test]
+    fn from_u64_and_from_i64_extremes() {
+        assert_eq!(
+            FieldElement::from_u64(u64::MAX).value,
+            BigInt::from(u64::MAX)
+        );
+        assert_eq!(
+            FieldElement::from_i64(i64::MIN).value,
+            Bn254::modulus() - BigInt::from(2u64).pow(63)
+        );
+        assert_eq!(
+            FieldElement::from_i64(i64::MIN),
+            -FieldElement::from_u64(1 << 63)
+        );
+        for n in [0, 1, -1, 42, i32::MIN, i32::MAX] {
+            assert_eq!(FieldElement::from_i32(n), FieldElement::from_i64(n as i64));
+        }
+    }
+
+    #[test]
+    fn integer_conversions_detect_overflow() {
+        let answer = FieldElement::from_i32(42);
+        assert_eq!(answer.try_to_u64(), Some(42));
+        assert_eq!(answer.try_to_i64(), Some(42));
+        assert_eq!(FieldElement::from_i64(-42).try_to_i64(), Some(-42));
+        assert_eq!(FieldElement::from_i64(-42).try_to_u64(), None);
+        assert_eq!(
+            FieldElement::from_u64(u64::MAX).try_to_u64(),
+            Some(u64::MAX)
+        );
+        assert_eq!(FieldElement::from_u64(u64::MAX).try_to_i64(), None);
+
+        let near_modulus = FieldElement::from_i32(-1);
+        assert_eq!(near_modulus.try_to_u64(), None);
+        assert_eq!(near_modulus.try_to_i64(), Some(-1));
+
+        // P / 2 is far from both ends of the field, so neither representative fits
+        let middle = FieldElement::new(Bn254::modulus() / 2);
+        assert_eq!(middle.try_to_u64(), None);
+        assert_eq!(middle.try_to_i64(), None);
+    }
+
+    #[test]
+    fn sorting_orders_by_canonical_representative() {
+        let mut values = Vec::from([
+            FieldElement::from_i32(-1),
+            FieldElement::from_i32(7),
+            FieldElement::zero(),
+            FieldElement::from_u64(u64::MAX),
+            FieldElement::from_i32(3),
+        ]);
+        values.sort();
+        let representatives: Vec<BigInt> = values.iter().map(|x| x.value.clone()).collect();
+        assert!(representatives.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(values[0], FieldElement::zero());
+        assert_eq!(values[4], FieldElement::from_i32(-1)); // P - 1 is the largest representative
+        assert!(FieldElement::from_i32(-1) > FieldElement::from_i32(1));
+    }
+
+    #[test]
+    fn equal_values_built_differently_share_a_hash_set_entry() {
+        let fives = [
+            FieldElement::from_i32(5),
+            FieldElement::from_i64(5),
+            FieldElement::from_u64(5),
+            FieldElement::from_dec_str("5").unwrap(),
+            FieldElement::new(Bn254::modulus() + 5),
+        ];
+        let set: std::collections::HashSet<FieldElement> = fives.into_iter().collect();
+        assert_eq!(set.len(), 1);
+
+        let mut map = std::collections::HashMap::new();
+        map.insert(FieldElement::from_i32(-1), "minus one");
+        assert_eq!(
+            map.get(&FieldElement::new(Bn254::modulus() - 1)),
+            Some(&"minus one")
+        );
+    }
+
+    #[test]
+    fn from_impls_reduce_like_the_named_constructors() {
+        assert_eq!(
+            FieldElement::from(u64::MAX),
+            FieldElement::from_u64(u64::MAX)
+        );
+        assert_eq!(FieldElement::from(-5i64), FieldElement::from_i64(-5));
+        assert_eq!(
+            FieldElement::from(3_000_000_000i64).value,
+            BigInt::from(3_000_000_000i64)
+        );
+        assert_eq!(
+            FieldElement::from(Bn254::modulus() * 2 + 9),
+            FieldElement::from_i32(9)
+        );
+        assert_eq!(
+            FieldElement::from(BigInt::from(-1)),
+            FieldElement::from_i32(-1)
+        );
+    }
+
+    #[test]
+    fn scalar_helpers_match_the_full_operators() {
+        let modulus = Bn254::modulus();
+        let scalars = [
+            BigInt::from(0),
+            BigInt::from(7),
+            BigInt::from(-3),
+            &modulus + BigInt::from(5),
+            -(&modulus * BigInt::from(2)) - BigInt::from(1),
+        ];
+        for x in [FieldElement::from_i32(11), FieldElement::from_i32(-1)] {
+            for k in &scalars {
+                let reduced = FieldElement::new(k.clone());
+                assert_eq!(x.scale(k), x.clone() * reduced.clone());
+                assert_eq!(x.add_scalar(k), x.clone() + reduced);
+            }
+        }
     }
 }
@@ -0,0 +1,47 @@
+use super::FieldElement;
+use ff_ce::{PrimeField, PrimeFieldRepr};
+use num_bigint::{BigInt, Sign};
+use poseidon_rs::Fr;
+
+impl FieldElement {
+    /// Converts into poseidon-rs's `Fr`. Both use the alt_bn128 scalar prime, so every element maps
+    /// to exactly one `Fr`. A hand-set `value` outside `[0, P)` is reduced first.
+    pub fn to_fr(&self) -> Fr {
+        // Unsigned encoding: the signed (two's complement) form grows a 33rd sign byte whenever the
+        // top bit is set. `read_le` reads 4 u64s, exactly the 32 bytes `to_bytes_le` produces.
+        let bytes = Self::new(self.value.clone()).to_bytes_le();
+        let mut repr = <Fr as PrimeField>::Repr::default();
+        repr.read_le(&bytes[..])
+            .expect("reading 32 bytes from a slice cannot fail");
+        Fr::from_repr(repr).expect("a reduced value is below the modulus")
+    }
+
+    /// Converts poseidon-rs's `Fr` back into a `FieldElement`
+    pub fn from_fr(fr: &Fr) -> Self {
+        let mut bytes = Vec::with_capacity(32);
+        fr.into_repr()
+            .write_le(&mut bytes)
+            .expect("writing to a Vec cannot fail");
+        Self::from_reduced(BigInt::from_bytes_le(Sign::Plus, &bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{Bn254, FieldParams};
+
+    #[test]
+    fn fr_round_trip_keeps_zero_and_values_near_the_modulus() {
+        let modulus = Bn254::modulus();
+        let values = [
+            FieldElement::zero(),
+            FieldElement::one(),
+            FieldElement::new(&modulus - BigInt::from(1)),
+            FieldElement::new(&modulus - BigInt::from(2)),
+        ];
+        for x in values {
+            assert_eq!(FieldElement::from_fr(&x.to_fr()), x);
+        }
+    }
+}
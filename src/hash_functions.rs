@@ -1,29 +1,210 @@
-use crate::field::FieldElement;
-use ff_ce::{Field, PrimeField, PrimeFieldRepr};
+use crate::field::{Bn254, FieldElement, FieldParams};
+use blake2::Blake2s256;
 use num_bigint::{BigInt, Sign};
-use poseidon_rs::{Fr, Poseidon};
+use poseidon_rs::{Constants, Fr, Poseidon, load_constants};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Why a hash could not be computed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HashError {
+    UnreducedInput(FieldElement), // Value outside [0, P), e.g. set by hand through the public `value` field
+    InputCount { got: usize, min: usize, max: usize }, // Arity the hash has no parameters for
+    Poseidon(String),             // Failure reported by poseidon-rs itself
+}
+
+impl fmt::Display for HashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashError::UnreducedInput(value) => write!(
+                f,
+                "FieldElement {} is not reduced modulo the field prime",
+                value.value
+            ),
+            HashError::InputCount { got, min, max } => {
+                write!(f, "hash supports {} to {} inputs, got {}", min, max, got)
+            }
+            HashError::Poseidon(message) => write!(f, "Poseidon hash failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for HashError {}
 
 ///Define a trait for hash functions
 pub trait HashFunction {
-    fn hash(&self, a: &FieldElement, b: &FieldElement) -> FieldElement;
+    /// Two-to-one compression, used by Merkle trees and the circuit's `Hash` gate
+    fn hash(&self, a: &FieldElement, b: &FieldElement) -> FieldElement {
+        self.hash_many(&[a.clone(), b.clone()])
+    }
+
+    /// Hashes a single element, e.g. for value commitments.
+    /// Defaults to `hash(a, 0)`; hashes with a native one-input mode override it.
+    fn hash_one(&self, a: &FieldElement) -> FieldElement {
+        self.hash(a, &FieldElement::zero())
+    }
+
+    /// Hashes any number of elements into one. `hash(a, b)` is `hash_many(&[a, b])`.
+    /// Panics where `try_hash_many` returns an error; use the `try_` forms for untrusted inputs.
+    fn hash_many(&self, inputs: &[FieldElement]) -> FieldElement {
+        self.try_hash_many(inputs)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Fallible form of `hash`
+    fn try_hash(&self, a: &FieldElement, b: &FieldElement) -> Result<FieldElement, HashError> {
+        self.try_hash_many(&[a.clone(), b.clone()])
+    }
+
+    /// Fallible form of `hash_many`: unreduced inputs or an unsupported input count are an error
+    fn try_hash_many(&self, inputs: &[FieldElement]) -> Result<FieldElement, HashError>;
+
+    /// Copies the hasher behind a `Box`, so owners of a `Box<dyn HashFunction>` can be cloned
+    fn clone_box(&self) -> Box<dyn HashFunction>;
+}
+
+impl Clone for Box<dyn HashFunction> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Round constants and MDS matrices for every arity, built on first use and shared by all instances
+static POSEIDON: OnceLock<Poseidon> = OnceLock::new();
+
+/// The same tables, readable here: `Poseidon` keeps its copy private
+static CONSTANTS: OnceLock<Constants> = OnceLock::new();
+
+/// Width and round counts for a fixed-arity Poseidon permutation.
+/// Round constants and the MDS matrix are circomlib's tables for `width`; only the number of rounds
+/// changes, taking a prefix of the round constants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoseidonParams {
+    pub width: usize,          // State size `t`; the hash takes exactly `t - 1` inputs
+    pub full_rounds: usize, // Rounds with an S-box on every element, split evenly before and after
+    pub partial_rounds: usize, // Rounds with an S-box on the first element only
+}
+
+impl PoseidonParams {
+    /// circomlib's `Poseidon(n_inputs)` parameters: `t = n_inputs + 1`, 8 full rounds and the
+    /// partial round count circomlib picks for that width
+    pub fn circomlib(n_inputs: usize) -> Self {
+        assert!(
+            (1..=16).contains(&n_inputs),
+            "circomlib Poseidon takes 1 to 16 inputs"
+        );
+        let constants = CONSTANTS.get_or_init(load_constants);
+        Self {
+            width: n_inputs + 1,
+            full_rounds: constants.n_rounds_f,
+            partial_rounds: constants.n_rounds_p[n_inputs - 1],
+        }
+    }
 }
 
 /// Production-grade Poseidon hash function
 /// Uses the BN254 curve's scalar field (same as used in many ZKP systems)
+#[derive(Clone)]
 pub struct PoseidonHash {
-    poseidon: Poseidon,
+    poseidon: &'static Poseidon,
+    params: Option<PoseidonParams>, // `None` picks circomlib's parameters for each arity
 }
 
 impl PoseidonHash {
-    /// Creates a new Poseidon hash instance
-    /// Uses default parameters optimized for 2 inputs
+    /// Creates a new Poseidon hash instance.
+    /// The parameter tables are loaded once per process, so creating instances and calling `hash`
+    /// repeatedly is cheap.
     pub fn new() -> Self {
         Self {
-            poseidon: Poseidon::new(),
+            poseidon: POSEIDON.get_or_init(Poseidon::new),
+            params: None,
+        }
+    }
+
+    /// Creates a Poseidon instance fixed to one width and round configuration.
+    /// Every call must then pass exactly `params.width - 1` inputs; `hash` needs a width of 3.
+    /// `with_params(PoseidonParams::circomlib(n))` hashes `n` inputs exactly like `new()`.
+    pub fn with_params(params: PoseidonParams) -> Self {
+        assert!(
+            (2..=17).contains(&params.width),
+            "Poseidon width must be between 2 and 17"
+        );
+        assert!(
+            params.full_rounds.is_multiple_of(2),
+            "full rounds are split evenly around the partial rounds"
+        );
+        let constants = CONSTANTS.get_or_init(load_constants);
+        let available = constants.c[params.width - 2].len() / params.width;
+        assert!(
+            params.full_rounds + params.partial_rounds <= available,
+            "only {} rounds of constants exist for width {}",
+            available,
+            params.width
+        );
+        Self {
+            poseidon: POSEIDON.get_or_init(Poseidon::new),
+            params: Some(params),
+        }
+    }
+
+    /// The fixed parameters, or `None` when each arity uses circomlib's defaults
+    pub fn params(&self) -> Option<PoseidonParams> {
+        self.params
+    }
+
+    /// Runs the permutation over `[0, inputs...]` with `params` and returns the first element
+    fn permute(&self, params: &PoseidonParams, inputs: Vec<Fr>) -> Fr {
+        let constants = CONSTANTS.get_or_init(load_constants);
+        let t = params.width;
+        let mut state = vec![FieldElement::zero().to_fr(); t];
+        state[1..].copy_from_slice(&inputs);
+
+        for i in 0..(params.full_rounds + params.partial_rounds) {
+            self.poseidon.ark(&mut state, &constants.c[t - 2], i * t);
+            self.poseidon
+                .sbox(params.full_rounds, params.partial_rounds, &mut state, i);
+            state = self.poseidon.mix(&state, &constants.m[t - 2]);
         }
+        state[0]
+    }
+
+    /// Hashes a message of any length to one field element.
+    ///
+    /// The message is padded with `0x01` and then zeros up to a multiple of 31 bytes (so every
+    /// chunk is below the modulus and no two messages pad to the same chunks). Starting from zero,
+    /// each chunk is absorbed with `state = Poseidon(state, chunk)` and the final state is the digest.
+    pub fn hash_bytes(&self, data: &[u8]) -> FieldElement {
+        let mut padded = data.to_vec();
+        padded.push(0x01);
+        padded.resize(padded.len().div_ceil(BYTES_PER_CHUNK) * BYTES_PER_CHUNK, 0);
+
+        let mut state = FieldElement::zero();
+        for chunk in padded.chunks_exact(BYTES_PER_CHUNK) {
+            let chunk = FieldElement::new(BigInt::from_bytes_le(Sign::Plus, chunk));
+            state = self.hash(&state, &chunk);
+        }
+        state
+    }
+
+    /// Commits to an ordered list of field elements with one digest.
+    ///
+    /// Absorbs the length and then each value with the same chaining as `hash_bytes`, starting
+    /// from zero. The length prefix keeps `[a]` and `[a, 0]` apart. Cheaper than a Merkle root, but
+    /// there are no inclusion proofs: opening one value means revealing the whole list.
+    pub fn commit_vec(&self, values: &[FieldElement]) -> FieldElement {
+        let length = FieldElement::from(values.len() as u64);
+        let mut state = self.hash(&FieldElement::zero(), &length);
+        for value in values {
+            state = self.hash(&state, value);
+        }
+        state
     }
 }
 
+/// Bytes absorbed per Poseidon call in `hash_bytes`: 248 bits always fit under the 254-bit prime
+const BYTES_PER_CHUNK: usize = 31;
+
 impl Default for PoseidonHash {
     fn default() -> Self {
         Self::new()
@@ -31,66 +212,659 @@ impl Default for PoseidonHash {
 }
 
 impl HashFunction for PoseidonHash {
-    fn hash(&self, a: &FieldElement, b: &FieldElement) -> FieldElement {
-        // Convert BigInt to Fr (field element)
-        let a_bytes = a.value.to_signed_bytes_le();
-        let b_bytes = b.value.to_signed_bytes_le();
-
-        // Pad or truncate to 32 bytes (Fr field size for BN254)
-        // Fr is 254 bits, so 32 bytes is enough.
-
-        let mut a_fr_repr = <Fr as PrimeField>::Repr::default();
-        let mut b_fr_repr = <Fr as PrimeField>::Repr::default();
-
-        // We need to read bytes into Repr. `read_le` expects a reader.
-        // We can just pad a_bytes manually to ensure it's correct size for read_le if needed,
-        // but read_le usually reads N bytes.
-        // Actually, let's just create a cursor or slice.
-
-        // Ensure inputs are within field modulus? `BigInt` could be larger.
-        // FieldElement already ensures it's mod P... wait, my FieldElement is mod BN128?
-        // poseidon-rs uses BN254?
-        // "BN128 scalar field size" in field.rs constant.
-        // poseidon-rs usually corresponds to BN254.
-        // They are often the same curve (alt_bn128).
-
-        let _ = a_fr_repr.read_le(&a_bytes[..]); // Ignore result, simple loading
-        let _ = b_fr_repr.read_le(&b_bytes[..]);
-
-        // Actually, to be safe against short inputs (BigInt small numbers):
-        let mut a_padded = a_bytes;
-        let mut b_padded = b_bytes;
-        // read_le expects 32 bytes for Fr?
-        // It reads 4 u64s.
-        // If my Bytes is shorter, `read_le` might fail or fill partially.
-        // Safest is to pad to 32 bytes.
-
-        // Re-do padding logic
-        let mut a_array = [0u8; 32];
-        let mut b_array = [0u8; 32];
-        let a_len = a_padded.len().min(32);
-        let b_len = b_padded.len().min(32);
-        a_array[..a_len].copy_from_slice(&a_padded[..a_len]);
-        b_array[..b_len].copy_from_slice(&b_padded[..b_len]);
-
-        a_fr_repr.read_le(&a_array[..]).unwrap();
-        b_fr_repr.read_le(&b_array[..]).unwrap();
-
-        // Create field elements from bytes
-        let a_fr = Fr::from_repr(a_fr_repr).unwrap_or(Fr::zero());
-        let b_fr = Fr::from_repr(b_fr_repr).unwrap_or(Fr::zero());
+    fn clone_box(&self) -> Box<dyn HashFunction> {
+        Box::new(self.clone())
+    }
+
+    /// Uses the arity-1 parameter set (`t = 2`), so the result differs from `hash(a, 0)`
+    fn hash_one(&self, a: &FieldElement) -> FieldElement {
+        self.hash_many(std::slice::from_ref(a))
+    }
+
+    /// Poseidon has a native parameter set (width `t = n + 1`) for every arity from 1 to 16,
+    /// so the inputs are absorbed in one permutation rather than chained pairwise.
+    /// An instance built with `with_params` accepts only its own arity.
+    fn try_hash_many(&self, inputs: &[FieldElement]) -> Result<FieldElement, HashError> {
+        match &self.params {
+            Some(params) => check_input_count(inputs, params.width - 1, params.width - 1)?,
+            None => check_input_count(inputs, 1, 16)?,
+        }
 
         // Hash using Poseidon
-        let inputs = vec![a_fr, b_fr];
-        let hash_result = self.poseidon.hash(inputs).expect("Poseidon hash failed");
+        let inputs = inputs
+            .iter()
+            .map(|input| check_reduced(input).map(|()| input.to_fr()))
+            .collect::<Result<Vec<Fr>, HashError>>()?;
+        let hash_result = match &self.params {
+            Some(params) => self.permute(params, inputs),
+            None => self.poseidon.hash(inputs).map_err(HashError::Poseidon)?,
+        };
+
+        Ok(FieldElement::from_fr(&hash_result))
+    }
+}
+
+/// Rejects values outside `[0, P)`. `value` is a public field, so it may have been set by hand.
+fn check_reduced(value: &FieldElement) -> Result<(), HashError> {
+    if value.value.sign() == Sign::Minus || value.value >= Bn254::modulus() {
+        return Err(HashError::UnreducedInput(value.clone()));
+    }
+    Ok(())
+}
+
+fn check_input_count(inputs: &[FieldElement], min: usize, max: usize) -> Result<(), HashError> {
+    if !(min..=max).contains(&inputs.len()) {
+        return Err(HashError::InputCount {
+            got: inputs.len(),
+            min,
+            max,
+        });
+    }
+    Ok(())
+}
+
+/// SHA-256 over the inputs' concatenated canonical 32-byte encodings, reduced into the field.
+/// Not SNARK-friendly, but widely compatible for trees that never go through a circuit.
+#[derive(Clone)]
+pub struct Sha256Hash;
+
+impl Sha256Hash {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for Sha256Hash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HashFunction for Sha256Hash {
+    fn clone_box(&self) -> Box<dyn HashFunction> {
+        Box::new(self.clone())
+    }
+
+    fn try_hash_many(&self, inputs: &[FieldElement]) -> Result<FieldElement, HashError> {
+        let mut hasher = Sha256::new();
+        for input in inputs {
+            check_reduced(input)?;
+            hasher.update(input.to_bytes_le());
+        }
+        let digest = hasher.finalize();
+
+        // Read the digest as a big-endian integer (the usual convention) and reduce mod P
+        Ok(FieldElement::new(BigInt::from_bytes_be(
+            Sign::Plus,
+            &digest,
+        )))
+    }
+}
+
+/// BLAKE2s-256 over the inputs' concatenated canonical 32-byte encodings, reduced into the field.
+/// A fast conventional hash for Merkle trees used for plain integrity checks rather than SNARKs.
+#[derive(Clone)]
+pub struct Blake2sHash;
+
+impl Blake2sHash {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for Blake2sHash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HashFunction for Blake2sHash {
+    fn clone_box(&self) -> Box<dyn HashFunction> {
+        Box::new(self.clone())
+    }
+
+    fn try_hash_many(&self, inputs: &[FieldElement]) -> Result<FieldElement, HashError> {
+        let mut hasher = Blake2s256::new();
+        for input in inputs {
+            check_reduced(input)?;
+            hasher.update(input.to_bytes_le());
+        }
+        let digest = hasher.finalize();
+
+        // Big-endian, like `Sha256Hash`
+        Ok(FieldElement::new(BigInt::from_bytes_be(
+            Sign::Plus,
+            &digest,
+        )))
+    }
+}
+
+/// Seed the MiMC round constants are derived from ("nothing up my sleeve")
+const MIMC_SEED: &[u8] = b"mini_zk_mimc_feistel";
+
+/// MiMC-Feistel over the BN128 scalar field, used as a sponge.
+///
+/// Each round computes `t = (xL + c_i)^5` and updates `(xL, xR) = (xR + t, xL)`; the exponent 5
+/// is coprime to `P - 1`, as in circomlib's MiMCSponge. Round constants come from
+/// `SHA-256(seed || i)` reduced mod P, so they are reproducible but not circomlib-compatible.
+#[derive(Clone)]
+pub struct MimcHash {
+    round_constants: Vec<FieldElement>,
+}
+
+impl MimcHash {
+    /// Creates a MiMC instance with the given number of Feistel rounds
+    pub fn new(rounds: usize) -> Self {
+        assert!(rounds > 0, "MiMC needs at least one round");
+        let round_constants = (0..rounds as u64)
+            .map(|i| {
+                let mut hasher = Sha256::new();
+                hasher.update(MIMC_SEED);
+                hasher.update(i.to_le_bytes());
+                FieldElement::new(BigInt::from_bytes_be(Sign::Plus, &hasher.finalize()))
+            })
+            .collect();
+        Self { round_constants }
+    }
+
+    /// Runs the Feistel network over `(left, right)`. The final round skips the swap.
+    fn permute(
+        &self,
+        mut left: FieldElement,
+        mut right: FieldElement,
+    ) -> (FieldElement, FieldElement) {
+        let last = self.round_constants.len() - 1;
+        for (i, constant) in self.round_constants.iter().enumerate() {
+            let t = (&left + constant).pow_u64(5);
+            if i < last {
+                let new_left = &right + &t;
+                right = left;
+                left = new_left;
+            } else {
+                right += t;
+            }
+        }
+        (left, right)
+    }
+}
+
+impl Default for MimcHash {
+    fn default() -> Self {
+        Self::new(91)
+    }
+}
+
+impl HashFunction for MimcHash {
+    fn clone_box(&self) -> Box<dyn HashFunction> {
+        Box::new(self.clone())
+    }
+
+    fn try_hash_many(&self, inputs: &[FieldElement]) -> Result<FieldElement, HashError> {
+        // Sponge with rate 1: absorb each input into the left half, permute, squeeze the left half
+        let (mut left, mut right) = (FieldElement::zero(), FieldElement::zero());
+        for input in inputs {
+            check_reduced(input)?;
+            (left, right) = self.permute(left + input.clone(), right);
+        }
+        Ok(left)
+    }
+}
+
+/// Seed the Pedersen generators are derived from ("nothing up my sleeve")
+const PEDERSEN_SEED: &[u8] = b"mini_zk_pedersen_generator";
+
+/// Most inputs a single `PedersenHash::hash_many` call accepts, like Poseidon
+const PEDERSEN_MAX_INPUTS: usize = 16;
+
+/// Inputs are split into two chunks of this many bits. Each chunk is below the subgroup order
+/// (~2^251), so no two inputs map to the same multiple of a generator.
+const PEDERSEN_CHUNK_BITS: u64 = 127;
+
+/// Pedersen hash on Baby Jubjub, the twisted Edwards curve defined over the BN128 scalar field
+/// (`168700x^2 + y^2 = 1 + 168696x^2y^2`), so point coordinates are ordinary `FieldElement`s.
+///
+/// Each input's two 127-bit chunks are decomposed into bits and the matching doublings of their
+/// own generator are accumulated; the input count is mixed in with one more generator so that
+/// trailing zeros still change the result. The output is the x-coordinate of the sum. Generators
+/// are derived by hashing `SHA-256(seed || i)` onto the curve and clearing the cofactor, so they
+/// are reproducible but not circomlib-compatible.
+#[derive(Clone)]
+pub struct PedersenHash {
+    generators: Vec<EdwardsPoint>, // [length, input0 low, input0 high, input1 low, ...]
+}
+
+impl PedersenHash {
+    pub fn new() -> Self {
+        let mut generators = Vec::with_capacity(2 * PEDERSEN_MAX_INPUTS + 1);
+        let mut counter = 0u64;
+        while generators.len() < 2 * PEDERSEN_MAX_INPUTS + 1 {
+            let mut hasher = Sha256::new();
+            hasher.update(PEDERSEN_SEED);
+            hasher.update(counter.to_le_bytes());
+            counter += 1;
+
+            let x = FieldElement::new(BigInt::from_bytes_be(Sign::Plus, &hasher.finalize()));
+            if let Some(point) = EdwardsPoint::from_x(x) {
+                // Multiply by the cofactor 8 to land in the prime-order subgroup
+                let point = point.double().double().double();
+                if !point.is_identity() {
+                    generators.push(point);
+                }
+            }
+        }
+        Self { generators }
+    }
+}
+
+impl Default for PedersenHash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HashFunction for PedersenHash {
+    fn clone_box(&self) -> Box<dyn HashFunction> {
+        Box::new(self.clone())
+    }
+
+    fn try_hash_many(&self, inputs: &[FieldElement]) -> Result<FieldElement, HashError> {
+        check_input_count(inputs, 0, PEDERSEN_MAX_INPUTS)?;
+
+        let length = BigInt::from(inputs.len());
+        let mut acc = self.generators[0].mul_bits(&length, 0, 64);
+        for (i, input) in inputs.iter().enumerate() {
+            check_reduced(input)?;
+            let low = &self.generators[2 * i + 1];
+            let high = &self.generators[2 * i + 2];
+            acc = acc.add(&low.mul_bits(&input.value, 0, PEDERSEN_CHUNK_BITS));
+            acc =
+                acc.add(&high.mul_bits(&input.value, PEDERSEN_CHUNK_BITS, 2 * PEDERSEN_CHUNK_BITS));
+        }
+        Ok(acc.affine_x())
+    }
+}
+
+/// Baby Jubjub point in projective coordinates `(X : Y : Z)`, standing for `(X/Z, Y/Z)`
+#[derive(Clone)]
+struct EdwardsPoint {
+    x: FieldElement,
+    y: FieldElement,
+    z: FieldElement,
+}
+
+impl EdwardsPoint {
+    fn a() -> FieldElement {
+        FieldElement::from_i32(168700)
+    }
+
+    fn d() -> FieldElement {
+        FieldElement::from_i32(168696)
+    }
+
+    fn identity() -> Self {
+        Self {
+            x: FieldElement::zero(),
+            y: FieldElement::one(),
+            z: FieldElement::one(),
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        self.x.is_zero() && self.y == self.z
+    }
+
+    /// The point with this x-coordinate, if any: `y^2 = (1 - a x^2) / (1 - d x^2)`
+    fn from_x(x: FieldElement) -> Option<Self> {
+        let x2 = &x * &x;
+        let numerator = FieldElement::one() - Self::a() * x2.clone();
+        let denominator = FieldElement::one() - Self::d() * x2;
+        let y = (numerator * denominator.inverse()?).sqrt()?;
+        Some(Self {
+            x,
+            y,
+            z: FieldElement::one(),
+        })
+    }
+
+    /// Unified projective addition (add-2008-bbjlp). Complete on Baby Jubjub because `a` is a
+    /// square and `d` is not, so it also handles doubling and the identity.
+    fn add(&self, other: &Self) -> Self {
+        let a = &self.z * &other.z;
+        let b = &a * &a;
+        let c = &self.x * &other.x;
+        let d = &self.y * &other.y;
+        let e = Self::d() * (&c * &d);
+        let f = b.clone() - e.clone();
+        let g = b + e;
+        let cross = (&self.x + &self.y) * (&other.x + &other.y) - c.clone() - d.clone();
+        Self {
+            x: &a * &(&f * &cross),
+            y: &a * &(&g * &(d - Self::a() * c)),
+            z: f * g,
+        }
+    }
 
-        // Convert result back to BigInt
-        let result_repr = hash_result.into_repr();
-        let mut result_bytes = Vec::new();
-        result_repr.write_le(&mut result_bytes).unwrap();
+    fn double(&self) -> Self {
+        self.add(self)
+    }
+
+    /// `scalar[from..to] * self`, i.e. the point times the integer formed by bits `from..to` of `scalar`
+    fn mul_bits(&self, scalar: &BigInt, from: u64, to: u64) -> Self {
+        let mut acc = Self::identity();
+        for i in (from..to).rev() {
+            acc = acc.double();
+            if scalar.bit(i) {
+                acc = acc.add(self);
+            }
+        }
+        acc
+    }
+
+    fn affine_x(&self) -> FieldElement {
+        let z_inverse = self
+            .z
+            .inverse()
+            .expect("complete addition never produces Z = 0");
+        &self.x * &z_inverse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blake2s_and_poseidon_hash_a_batch_deterministically() {
+        let pairs: Vec<(FieldElement, FieldElement)> = (0..1000)
+            .map(|i| (FieldElement::from_i32(i), FieldElement::from_i32(-i - 1)))
+            .collect();
+        let hashers: [Box<dyn HashFunction>; 2] =
+            [Box::new(Blake2sHash::new()), Box::new(PoseidonHash::new())];
+        let batches: Vec<Vec<FieldElement>> = hashers
+            .iter()
+            .map(|hasher| pairs.iter().map(|(a, b)| hasher.hash(a, b)).collect())
+            .collect();
+
+        for (hasher, batch) in hashers.iter().zip(&batches) {
+            assert!(batch.iter().all(|digest| digest.value < Bn254::modulus()));
+            let again: Vec<FieldElement> = pairs.iter().map(|(a, b)| hasher.hash(a, b)).collect();
+            assert_eq!(*batch, again);
+        }
+        assert!(batches[0].iter().zip(&batches[1]).all(|(b, p)| b != p));
+    }
+
+    #[test]
+    fn blake2s_hash_is_deterministic_and_reduced() {
+        let hasher = Blake2sHash::new();
+        let (a, b) = (FieldElement::from_i32(1), FieldElement::from_i32(2));
+        let digest = hasher.hash(&a, &b);
+        assert_eq!(digest, hasher.hash(&a, &b));
+        assert_eq!(
+            digest,
+            FieldElement::from_dec_str(
+                "9227305580375968808349764197566588961006102544570683157510237187250153210231"
+            )
+            .unwrap()
+        );
+        assert_ne!(digest, hasher.hash(&b, &a));
+        assert!(digest.value < Bn254::modulus());
+    }
+
+    #[test]
+    fn poseidon_hashes_values_near_the_modulus() {
+        use ff_ce::PrimeField;
+
+        let hasher = PoseidonHash::new();
+        let near_modulus = FieldElement::from_i32(-1);
+        let digest = hasher.hash(&near_modulus, &FieldElement::one());
+        assert_eq!(digest, hasher.hash(&near_modulus, &FieldElement::one()));
+
+        // Reference: poseidon-rs fed the decimal value directly, bypassing the byte conversion
+        let reference = Poseidon::new()
+            .hash(vec![
+                Fr::from_str(&near_modulus.to_string()).unwrap(),
+                Fr::from_str("1").unwrap(),
+            ])
+            .unwrap();
+        assert_eq!(digest, FieldElement::from_fr(&reference));
+        assert_eq!(FieldElement::from_fr(&near_modulus.to_fr()), near_modulus);
+
+        // circomlib's poseidon([1, 2])
+        assert_eq!(
+            hasher.hash(&FieldElement::from_i32(1), &FieldElement::from_i32(2)),
+            FieldElement::from_dec_str(
+                "7853200120776062878684798364095072458815029376092732009249414926327459813530"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn sha256_hash_is_deterministic_and_differs_from_poseidon() {
+        let hasher = Sha256Hash::new();
+        let (a, b) = (FieldElement::from_i32(1), FieldElement::from_i32(2));
+        let digest = hasher.hash(&a, &b);
+        assert_eq!(digest, hasher.hash(&a, &b));
+        assert_ne!(digest, hasher.hash(&b, &a));
+        assert_ne!(digest, PoseidonHash::new().hash(&a, &b));
+        assert!(digest.value < Bn254::modulus());
+    }
+
+    #[test]
+    fn mimc_is_deterministic_and_collision_free_on_a_sample() {
+        let hasher = MimcHash::default();
+        let (a, b) = (FieldElement::from_i32(1), FieldElement::from_i32(2));
+        assert_eq!(hasher.hash(&a, &b), hasher.hash(&a, &b));
+        assert_ne!(hasher.hash(&a, &b), MimcHash::new(10).hash(&a, &b));
+
+        let mut digests = std::collections::HashSet::new();
+        for x in 0..16 {
+            for y in 0..16 {
+                let digest = hasher.hash(&FieldElement::from_i32(x), &FieldElement::from_i32(y));
+                assert!(digests.insert(digest), "collision at ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn poseidon_hashes_one_three_and_four_inputs() {
+        let hasher = PoseidonHash::new();
+        let inputs: Vec<FieldElement> = (1..=4).map(FieldElement::from_i32).collect();
+        // circomlib's poseidon([1]), poseidon([1, 2, 3]) and poseidon([1, 2, 3, 4])
+        let expected = [
+            (
+                1,
+                "18586133768512220936620570745912940619677854269274689475585506675881198879027",
+            ),
+            (
+                3,
+                "6542985608222806190361240322586112750744169038454362455181422643027100751666",
+            ),
+            (
+                4,
+                "18821383157269793795438455681495246036402687001665670618754263018637548127333",
+            ),
+        ];
+        for (n, digest) in expected {
+            assert_eq!(
+                hasher.hash_many(&inputs[..n]),
+                FieldElement::from_dec_str(digest).unwrap()
+            );
+        }
+
+        assert_eq!(
+            hasher.hash(&inputs[0], &inputs[1]),
+            hasher.hash_many(&inputs[..2])
+        );
+        assert_eq!(
+            hasher.try_hash_many(&[]),
+            Err(HashError::InputCount {
+                got: 0,
+                min: 1,
+                max: 16
+            })
+        );
+    }
+
+    #[test]
+    fn poseidon_tables_are_shared_across_10k_hashes() {
+        let first = PoseidonHash::new();
+        let mut state = FieldElement::zero();
+        for i in 0..10_000 {
+            let hasher = PoseidonHash::new();
+            assert!(std::ptr::eq(hasher.poseidon, first.poseidon));
+            state = hasher.hash(&state, &FieldElement::from_i32(i));
+        }
+        assert!(!state.is_zero());
+    }
+
+    #[test]
+    fn poseidon_hash_one_uses_its_own_parameters() {
+        let hasher = PoseidonHash::new();
+        let x = FieldElement::from_i32(42);
+        assert_eq!(hasher.hash_one(&x), hasher.hash_one(&x));
+        assert_eq!(
+            hasher.hash_one(&x),
+            hasher.hash_many(std::slice::from_ref(&x))
+        );
+        assert_ne!(hasher.hash_one(&x), hasher.hash(&x, &FieldElement::zero()));
+
+        // Hashes without a one-input mode fall back to `hash(x, 0)`
+        let sha256 = Sha256Hash::new();
+        assert_eq!(sha256.hash_one(&x), sha256.hash(&x, &FieldElement::zero()));
+    }
+
+    #[test]
+    fn pedersen_is_deterministic_and_order_sensitive() {
+        let hasher = PedersenHash::new();
+        let (a, b) = (FieldElement::from_i32(5), FieldElement::from_i32(9));
+        assert_eq!(hasher.hash(&a, &b), hasher.hash(&a, &b));
+        assert_ne!(hasher.hash(&a, &b), hasher.hash(&b, &a));
+        // The input count is mixed in, so a trailing zero still changes the digest
+        assert_ne!(
+            hasher.hash_many(std::slice::from_ref(&a)),
+            hasher.hash(&a, &FieldElement::zero())
+        );
+        assert!(hasher.try_hash_many(&vec![a; 17]).is_err());
+    }
+
+    #[test]
+    fn poseidon_accepts_reduced_inputs_and_rejects_others() {
+        use ff_ce::PrimeField;
+
+        let hasher = PoseidonHash::new();
+        // circomlib's poseidon([0, 0])
+        assert_eq!(
+            hasher.hash(&FieldElement::zero(), &FieldElement::zero()),
+            FieldElement::from_dec_str(
+                "14744269619966411208579211824598458697587494354926760081771325075741142829156"
+            )
+            .unwrap()
+        );
+        // P - 2 against poseidon-rs fed the decimal value directly
+        let large = FieldElement::from_i32(-2);
+        let large_fr = Fr::from_str(&large.to_string()).unwrap();
+        let reference = Poseidon::new().hash(vec![large_fr, large_fr]).unwrap();
+        assert_eq!(
+            hasher.hash(&large, &large),
+            FieldElement::from_fr(&reference)
+        );
+        assert_eq!(
+            hasher.hash(&large, &large),
+            FieldElement::from_dec_str(
+                "14727486852435655284355082328759293765298622680655941431712712959445987377141"
+            )
+            .unwrap()
+        );
+
+        let mut unreduced = FieldElement::zero();
+        unreduced.value = Bn254::modulus();
+        assert_eq!(
+            hasher.try_hash(&unreduced, &FieldElement::one()),
+            Err(HashError::UnreducedInput(unreduced.clone()))
+        );
+        let mut negative = FieldElement::zero();
+        negative.value = BigInt::from(-1);
+        assert!(hasher.try_hash(&FieldElement::one(), &negative).is_err());
+    }
+
+    #[test]
+    fn hash_bytes_is_deterministic_for_any_length() {
+        let hasher = PoseidonHash::new();
+        let messages: [&[u8]; 5] = [b"", b"one chunk", &[7; 30], &[7; 31], &[7; 100]];
+        let digests: Vec<FieldElement> = messages.iter().map(|m| hasher.hash_bytes(m)).collect();
+        for (message, digest) in messages.iter().zip(&digests) {
+            assert_eq!(hasher.hash_bytes(message), *digest);
+        }
+        let distinct: std::collections::HashSet<_> = digests.iter().collect();
+        assert_eq!(distinct.len(), messages.len());
+
+        // The empty message is one padding chunk: 0x01 followed by zeros
+        let padding = FieldElement::one();
+        assert_eq!(digests[0], hasher.hash(&FieldElement::zero(), &padding));
+        // Trailing zero bytes are not lost to the padding
+        assert_ne!(hasher.hash_bytes(b"a"), hasher.hash_bytes(b"a\0"));
+    }
+
+    #[test]
+    fn rejected_inputs_are_errors_not_panics() {
+        let mut unreduced = FieldElement::zero();
+        unreduced.value = Bn254::modulus() + 1;
+        let one = FieldElement::one();
+        let hashers: [Box<dyn HashFunction>; 5] = [
+            Box::new(PoseidonHash::new()),
+            Box::new(Sha256Hash::new()),
+            Box::new(Blake2sHash::new()),
+            Box::new(MimcHash::default()),
+            Box::new(PedersenHash::new()),
+        ];
+        for hasher in &hashers {
+            assert_eq!(
+                hasher.try_hash(&unreduced, &one),
+                Err(HashError::UnreducedInput(unreduced.clone()))
+            );
+            assert!(hasher.try_hash(&one, &one).is_ok());
+        }
+
+        let fixed = PoseidonHash::with_params(PoseidonParams::circomlib(2));
+        let error = fixed.try_hash_many(std::slice::from_ref(&one)).unwrap_err();
+        assert_eq!(error.to_string(), "hash supports 2 to 2 inputs, got 1");
+        assert_eq!(fixed.hash(&one, &one), PoseidonHash::new().hash(&one, &one));
+    }
+
+    #[test]
+    fn poseidon_round_counts_change_the_hash() {
+        let (a, b) = (FieldElement::from_i32(1), FieldElement::from_i32(2));
+        let default = PoseidonHash::with_params(PoseidonParams::circomlib(2));
+        let fewer_rounds = PoseidonHash::with_params(PoseidonParams {
+            width: 3,
+            full_rounds: 8,
+            partial_rounds: 40,
+        });
+
+        assert_ne!(default.hash(&a, &b), fewer_rounds.hash(&a, &b));
+        // The circomlib parameters still produce circomlib's poseidon([1, 2])
+        assert_eq!(default.hash(&a, &b), PoseidonHash::new().hash(&a, &b));
+        assert_eq!(
+            default.hash(&a, &b),
+            FieldElement::from_dec_str(
+                "7853200120776062878684798364095072458815029376092732009249414926327459813530"
+            )
+            .unwrap()
+        );
+    }
 
-        let result_bigint = BigInt::from_bytes_le(Sign::Plus, &result_bytes);
+    #[test]
+    fn commit_vec_binds_order_and_length() {
+        let hasher = PoseidonHash::new();
+        let (a, b) = (FieldElement::from_i32(5), FieldElement::from_i32(9));
 
-        FieldElement::new(result_bigint)
+        let commitment = hasher.commit_vec(&[a.clone(), b.clone()]);
+        assert_eq!(commitment, hasher.commit_vec(&[a.clone(), b.clone()]));
+        assert_ne!(commitment, hasher.commit_vec(&[b, a.clone()]));
+        assert_ne!(
+            hasher.commit_vec(std::slice::from_ref(&a)),
+            hasher.commit_vec(&[a, FieldElement::zero()])
+        );
     }
 }
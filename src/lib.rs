@@ -0,0 +1,9 @@
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod circuit;
+pub mod field;
+pub mod hash_functions;
+pub mod merkle_tree;
+pub mod proof;
+pub mod r1cs;
+pub mod transcript;
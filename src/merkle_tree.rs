@@ -1,32 +1,133 @@
-use crate::field::FieldElement;
-use crate::hash_functions::{HashFunction, PoseidonHash};
+use crate::field::{FieldElement, ParseError};
+use crate::hash_functions::{HashError, HashFunction, PoseidonHash};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub mod sparse;
 
 pub struct MerkleTree {
     pub leaves: Vec<FieldElement>,
     pub levels: Vec<Vec<FieldElement>>,
     pub root: FieldElement,
+    hasher: Box<dyn HashFunction>, // Same pluggable hasher type as `Circuit`, so trees and circuits agree
+}
+
+/// Inclusion proof for several leaves at once, from `MerkleTree::get_multi_proof`.
+///
+/// `siblings` holds only the nodes the verifier cannot compute itself, level by level from the
+/// leaves up and left to right within a level. Nodes shared by several paths appear once, and a
+/// trailing node on an odd-sized level (its own sibling) is never included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiProof {
+    pub indices: Vec<usize>, // Leaf indices covered, ascending and without duplicates
+    pub siblings: Vec<FieldElement>,
+}
+
+/// Errors raised by `MerkleTree::from_file`
+#[derive(Debug)]
+pub enum LeafFileError {
+    Io(std::io::Error),
+    Parse { line: usize, error: ParseError }, // 1-based line number of the malformed leaf
+    Empty,                                    // The file holds no leaves
+}
+
+impl fmt::Display for LeafFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeafFileError::Io(e) => write!(f, "leaf file I/O failed: {}", e),
+            LeafFileError::Parse { line, error } => write!(f, "line {}: {}", line, error),
+            LeafFileError::Empty => write!(f, "leaf file contains no leaves"),
+        }
+    }
+}
+
+impl std::error::Error for LeafFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LeafFileError::Io(e) => Some(e),
+            LeafFileError::Parse { error, .. } => Some(error),
+            LeafFileError::Empty => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for LeafFileError {
+    fn from(e: std::io::Error) -> Self {
+        LeafFileError::Io(e)
+    }
+}
+
+/// On-disk form of a `MerkleTree`. The hasher is not stored: the caller supplies it again.
+#[derive(Serialize, Deserialize)]
+struct StoredTree {
+    levels: Vec<Vec<FieldElement>>,
+    root: FieldElement,
 }
 
 impl MerkleTree {
+    /// Builds a tree hashed with Poseidon
     pub fn new(leaves: Vec<FieldElement>) -> Self {
-        let hasher = PoseidonHash::new();
+        Self::with_hasher(leaves, Box::new(PoseidonHash::new()))
+    }
+
+    /// Builds a Poseidon tree from a text file with one decimal leaf per line.
+    /// Surrounding whitespace and blank lines are ignored; a malformed line is reported with its number.
+    pub fn from_file(path: &str) -> Result<Self, LeafFileError> {
+        let contents = std::fs::read_to_string(path)?;
+        let mut leaves = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let leaf = FieldElement::from_dec_str(line).map_err(|error| LeafFileError::Parse {
+                line: index + 1,
+                error,
+            })?;
+            leaves.push(leaf);
+        }
+        if leaves.is_empty() {
+            return Err(LeafFileError::Empty);
+        }
+        Ok(Self::new(leaves))
+    }
+
+    /// Builds a Poseidon tree after padding the leaves with `pad_value` up to the next power of two.
+    /// Every leaf then sits at the same depth, so proofs have the same length for any leaf count
+    /// up to that power of two.
+    pub fn with_padding(mut leaves: Vec<FieldElement>, pad_value: FieldElement) -> Self {
+        assert!(!leaves.is_empty(), "A Merkle tree needs at least one leaf");
+        leaves.resize(leaves.len().next_power_of_two(), pad_value);
+        Self::new(leaves)
+    }
+
+    /// Builds a tree using a custom hash function for every internal node.
+    ///
+    /// Levels with an odd number of nodes pair their last node with itself (`hash(x, x)`), and
+    /// `get_proof` returns that same node as the sibling, so every leaf has a verifiable path.
+    /// Panics if a node cannot be hashed; see `try_with_hasher`.
+    pub fn with_hasher(leaves: Vec<FieldElement>, hasher: Box<dyn HashFunction>) -> Self {
+        Self::try_with_hasher(leaves, hasher).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Same as `with_hasher`, but a leaf the hash function rejects (e.g. an unreduced value) is an error
+    pub fn try_with_hasher(
+        leaves: Vec<FieldElement>,
+        hasher: Box<dyn HashFunction>,
+    ) -> Result<Self, HashError> {
+        assert!(!leaves.is_empty(), "A Merkle tree needs at least one leaf");
+
         let mut levels = vec![leaves.clone()];
         let mut current_level = leaves.clone();
 
         while current_level.len() > 1 {
             let mut next_level = Vec::new();
 
-            // Should be even number of leaves for simplicity in this basic implementation
-            // If odd, we could duplicate the last one, but let's assume even for now as per tutorial
             for i in (0..current_level.len()).step_by(2) {
                 let left = &current_level[i];
-                let right = if i + 1 < current_level.len() {
-                    &current_level[i + 1]
-                } else {
-                    &current_level[i] // Duplicate if odd
-                };
+                let right = sibling(&current_level, i); // Duplicates the last node if odd
 
-                let hash = hasher.hash(left, right);
+                let hash = hasher.try_hash(left, right)?;
                 next_level.push(hash);
             }
 
@@ -36,17 +137,161 @@ impl MerkleTree {
 
         let root = current_level[0].clone();
 
-        Self {
+        Ok(Self {
             leaves,
             levels,
             root,
+            hasher,
+        })
+    }
+
+    /// Root of the tree `with_hasher` would build over `leaves`, computed in a single buffer that
+    /// is folded in place level by level. No levels are kept, so this mode cannot produce proofs;
+    /// use it when only the root of a large leaf set is needed.
+    pub fn compute_root_only(leaves: &[FieldElement], hasher: &dyn HashFunction) -> FieldElement {
+        assert!(!leaves.is_empty(), "A Merkle tree needs at least one leaf");
+
+        let mut nodes = leaves.to_vec();
+        let mut len = nodes.len();
+        while len > 1 {
+            for i in (0..len).step_by(2) {
+                let parent = hasher.hash(&nodes[i], sibling(&nodes[..len], i));
+                nodes[i / 2] = parent; // Slot i / 2 <= i has already been read
+            }
+            len = len.div_ceil(2);
+        }
+        nodes.swap_remove(0)
+    }
+
+    /// Encodes every level and the root with bincode, so the tree can be reloaded without rehashing.
+    /// The hash function is not recorded; pass the same one to `deserialize_with_hasher`.
+    pub fn serialize(&self) -> Vec<u8> {
+        let stored = StoredTree {
+            levels: self.levels.clone(),
+            root: self.root.clone(),
+        };
+        bincode::serialize(&stored).expect("a Merkle tree is always serializable")
+    }
+
+    /// Reloads a Poseidon tree written by `serialize`
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        Self::deserialize_with_hasher(bytes, Box::new(PoseidonHash::new()))
+    }
+
+    /// Reloads a tree written by `serialize` that was built with `hasher`.
+    /// The level shapes are checked, but the stored hashes are trusted rather than recomputed.
+    pub fn deserialize_with_hasher(
+        bytes: &[u8],
+        hasher: Box<dyn HashFunction>,
+    ) -> Result<Self, bincode::Error> {
+        let StoredTree { levels, root } = bincode::deserialize(bytes)?;
+
+        let well_formed = !levels.is_empty()
+            && !levels[0].is_empty()
+            && levels
+                .windows(2)
+                .all(|pair| pair[1].len() == pair[0].len().div_ceil(2))
+            && levels
+                .last()
+                .is_some_and(|top| top.len() == 1 && top[0] == root);
+        if !well_formed {
+            return Err(Box::new(bincode::ErrorKind::Custom(
+                "stored levels do not form a Merkle tree".to_string(),
+            )));
         }
+
+        Ok(Self {
+            leaves: levels[0].clone(),
+            levels,
+            root,
+            hasher,
+        })
+    }
+
+    /// Number of hashing levels between the leaves and the root (the length of every proof)
+    pub fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    /// Most leaves a tree of the current depth holds (`2^depth`); `push_leaf` past this adds a level
+    pub fn capacity(&self) -> usize {
+        1 << self.depth()
+    }
+
+    /// Whether `index` names an existing leaf, i.e. one `get_proof` and `update_leaf` accept
+    pub fn is_valid_index(&self, index: usize) -> bool {
+        index < self.leaves.len()
+    }
+
+    /// The leaves in insertion order
+    pub fn iter_leaves(&self) -> impl Iterator<Item = &FieldElement> {
+        self.leaves.iter()
+    }
+
+    /// Nodes of one level, left to right: level 0 is the leaves and level `depth()` holds only the
+    /// root. `None` past the root.
+    pub fn iter_level(&self, level: usize) -> Option<impl Iterator<Item = &FieldElement>> {
+        self.levels.get(level).map(|nodes| nodes.iter())
     }
 
     pub fn get_root(&self) -> FieldElement {
         self.root.clone()
     }
 
+    /// The hash function the tree was built with
+    pub fn hasher(&self) -> &dyn HashFunction {
+        self.hasher.as_ref()
+    }
+
+    /// Replaces one leaf and rehashes only its path to the root (O(log n) hashes)
+    pub fn update_leaf(&mut self, index: usize, value: FieldElement) {
+        assert!(
+            index < self.leaves.len(),
+            "Leaf index {} out of range for {} leaves",
+            index,
+            self.leaves.len()
+        );
+        self.leaves[index] = value.clone();
+        self.levels[0][index] = value;
+        self.rehash_path(index);
+    }
+
+    /// Appends a leaf, growing the tree by a level when the leaf count passes a power of two
+    pub fn push_leaf(&mut self, value: FieldElement) {
+        self.leaves.push(value.clone());
+        self.levels[0].push(value);
+        self.rehash_path(self.leaves.len() - 1);
+    }
+
+    /// Recomputes every ancestor of `levels[0][index]`, extending levels that grew and adding a new
+    /// root level if the old root gained a sibling. The result matches a full rebuild.
+    fn rehash_path(&mut self, mut index: usize) {
+        let mut height = 0;
+        while self.levels[height].len() > 1 {
+            let level = &self.levels[height];
+            let left_index = index - index % 2;
+            let parent = self
+                .hasher
+                .hash(&level[left_index], sibling(level, left_index));
+
+            if height + 1 == self.levels.len() {
+                self.levels.push(Vec::new());
+            }
+            let parent_index = index / 2;
+            let next_level = &mut self.levels[height + 1];
+            if parent_index < next_level.len() {
+                next_level[parent_index] = parent;
+            } else {
+                next_level.push(parent);
+            }
+
+            index = parent_index;
+            height += 1;
+        }
+
+        self.root = self.levels[height][0].clone();
+    }
+
     /// Returns the Merkle Path for a given leaf index.
     /// The path consists of the sibling nodes needed to recompute the root.
     pub fn get_proof(&self, mut index: usize) -> Vec<FieldElement> {
@@ -54,22 +299,362 @@ impl MerkleTree {
 
         // Iterate through levels (excluding the root)
         for level in &self.levels[0..self.levels.len() - 1] {
-            let encoded_sibling = if index % 2 == 0 {
-                // We are left, sibling is right
-                if index + 1 < level.len() {
-                    level[index + 1].clone()
-                } else {
-                    level[index].clone() // Duplicate
-                }
+            path.push(sibling(level, index).clone());
+            index /= 2;
+        }
+
+        path
+    }
+
+    /// Same path as `get_proof`, with each sibling paired with a flag that is `true` when the
+    /// current node is the right child at that level (i.e. the sibling goes on the left).
+    /// The flags are the bits of `index`, least significant first.
+    pub fn get_proof_with_directions(&self, index: usize) -> Vec<(FieldElement, bool)> {
+        self.get_proof(index)
+            .into_iter()
+            .enumerate()
+            .map(|(level, sibling)| (sibling, (index >> level) & 1 == 1))
+            .collect()
+    }
+
+    /// Checks a Merkle path produced by `get_proof` by hashing `leaf` up to the root.
+    /// At each level the low bit of `index` says whether the current node is the left (0) or right (1) child.
+    pub fn verify_proof(&self, leaf: &FieldElement, index: usize, path: &[FieldElement]) -> bool {
+        if index >= self.leaves.len() || path.len() != self.levels.len() - 1 {
+            return false;
+        }
+
+        let mut current = leaf.clone();
+        let mut index = index;
+        for sibling in path {
+            let parent = if index.is_multiple_of(2) {
+                self.hasher.try_hash(&current, sibling)
             } else {
-                // We are right, sibling is left
-                level[index - 1].clone()
+                self.hasher.try_hash(sibling, &current)
             };
-
-            path.push(encoded_sibling);
+            // A value the hash function rejects cannot be on a valid path
+            let Ok(parent) = parent else {
+                return false;
+            };
+            current = parent;
             index /= 2;
         }
 
+        current == self.root
+    }
+
+    /// Builds one proof covering all `indices` (duplicates are ignored)
+    pub fn get_multi_proof(&self, indices: &[usize]) -> MultiProof {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        assert!(
+            indices.last().is_none_or(|last| *last < self.leaves.len()),
+            "Leaf index out of range"
+        );
+
+        let mut siblings = Vec::new();
+        let mut known = indices.clone();
+        for level in &self.levels[0..self.levels.len() - 1] {
+            let mut parents = Vec::new();
+            let mut i = 0;
+            while i < known.len() {
+                let index = known[i];
+                let pair_known = index.is_multiple_of(2) && known.get(i + 1) == Some(&(index + 1));
+                let self_paired = index.is_multiple_of(2) && index + 1 == level.len();
+                if pair_known {
+                    i += 1; // Both children are known, the parent needs nothing from the proof
+                } else if !self_paired {
+                    siblings.push(sibling(level, index).clone());
+                }
+                parents.push(index / 2);
+                i += 1;
+            }
+            known = parents;
+        }
+
+        MultiProof { indices, siblings }
+    }
+
+    /// Checks a proof from `get_multi_proof`. `leaves` are the claimed values at `proof.indices`, in the same order.
+    pub fn verify_multi_proof(&self, leaves: &[FieldElement], proof: &MultiProof) -> bool {
+        let ascending = proof.indices.windows(2).all(|pair| pair[0] < pair[1]);
+        let in_range = proof
+            .indices
+            .last()
+            .is_none_or(|last| *last < self.leaves.len());
+        if leaves.is_empty() || leaves.len() != proof.indices.len() || !ascending || !in_range {
+            return false;
+        }
+
+        let mut siblings = proof.siblings.iter();
+        let mut nodes: Vec<(usize, FieldElement)> = proof
+            .indices
+            .iter()
+            .copied()
+            .zip(leaves.iter().cloned())
+            .collect();
+        for level in &self.levels[0..self.levels.len() - 1] {
+            let mut parents = Vec::new();
+            let mut i = 0;
+            while i < nodes.len() {
+                let (index, node) = &nodes[i];
+                let parent = if index.is_multiple_of(2) {
+                    let right = match nodes.get(i + 1) {
+                        Some((next, right)) if *next == index + 1 => {
+                            i += 1;
+                            right
+                        }
+                        _ if index + 1 == level.len() => node, // Trailing node pairs with itself
+                        _ => match siblings.next() {
+                            Some(right) => right,
+                            None => return false,
+                        },
+                    };
+                    self.hasher.try_hash(node, right)
+                } else {
+                    match siblings.next() {
+                        Some(left) => self.hasher.try_hash(left, node),
+                        None => return false,
+                    }
+                };
+                let Ok(parent) = parent else {
+                    return false;
+                };
+                parents.push((index / 2, parent));
+                i += 1;
+            }
+            nodes = parents;
+        }
+
+        siblings.next().is_none() && nodes.len() == 1 && nodes[0].1 == self.root
+    }
+}
+
+/// The node `level[index]` is hashed with. Shared by tree construction and `get_proof` so the
+/// two can never disagree: a trailing node on an odd-sized level is its own sibling.
+fn sibling(level: &[FieldElement], index: usize) -> &FieldElement {
+    if index.is_multiple_of(2) {
+        // We are left, sibling is right (or ourselves if there is no right node)
+        level.get(index + 1).unwrap_or(&level[index])
+    } else {
+        // We are right, sibling is left
+        &level[index - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_functions::Sha256Hash;
+
+    fn leaves(n: i32) -> Vec<FieldElement> {
+        (1..=n).map(|i| FieldElement::from_i32(i * 1001)).collect()
+    }
+
+    #[test]
+    fn custom_hasher_changes_the_root() {
+        let poseidon = MerkleTree::new(leaves(4));
+        let sha256 = MerkleTree::with_hasher(leaves(4), Box::new(Sha256Hash::new()));
+        assert_ne!(sha256.get_root(), poseidon.get_root());
+
+        let (a, b) = (leaves(4)[0].clone(), leaves(4)[1].clone());
+        assert_eq!(sha256.levels[1][0], Sha256Hash::new().hash(&a, &b));
+        assert!(sha256.verify_proof(&a, 0, &sha256.get_proof(0)));
+    }
+
+    #[test]
+    fn verify_proof_rejects_tampered_leaves_and_wrong_indices() {
+        let tree = MerkleTree::new(leaves(4));
+        let leaf = tree.leaves[2].clone();
+        let path = tree.get_proof(2);
+        assert!(tree.verify_proof(&leaf, 2, &path));
+
+        assert!(!tree.verify_proof(&FieldElement::from_i32(7), 2, &path));
+        assert!(!tree.verify_proof(&leaf, 3, &path));
+        assert!(!tree.verify_proof(&leaf, 4, &path));
+        assert!(!tree.verify_proof(&leaf, 2, &path[..1]));
+    }
+
+    #[test]
+    fn directions_are_the_bits_of_the_index() {
+        let tree = MerkleTree::new(leaves(8));
+        for index in 0..8 {
+            let proof = tree.get_proof_with_directions(index);
+            assert_eq!(proof.len(), 3);
+            for (level, (sibling, is_right)) in proof.iter().enumerate() {
+                assert_eq!(*is_right, (index >> level) & 1 == 1, "index {}", index);
+                assert_eq!(*sibling, tree.get_proof(index)[level]);
+            }
+        }
+        let bits: Vec<bool> = tree
+            .get_proof_with_directions(6)
+            .into_iter()
+            .map(|(_, is_right)| is_right)
+            .collect();
+        assert_eq!(bits, [false, true, true]); // 6 = 0b110, least significant bit first
+    }
+
+    #[test]
+    fn every_leaf_of_an_odd_sized_tree_verifies() {
+        for n in [3, 5, 7] {
+            let tree = MerkleTree::new(leaves(n));
+            for (index, leaf) in tree.leaves.iter().enumerate() {
+                assert!(
+                    tree.verify_proof(leaf, index, &tree.get_proof(index)),
+                    "leaf {} of {}",
+                    index,
+                    n
+                );
+            }
+        }
+
+        // The trailing leaf of 3 is hashed with itself
+        let tree = MerkleTree::new(leaves(3));
+        let last = &tree.leaves[2];
+        assert_eq!(tree.get_proof(2)[0], *last);
+        assert_eq!(tree.levels[1][1], PoseidonHash::new().hash(last, last));
+    }
+
+    #[test]
+    fn incremental_updates_match_a_rebuild() {
+        let mut values = leaves(5);
+        let mut tree = MerkleTree::new(values.clone());
+        tree.update_leaf(3, FieldElement::from_i32(77));
+        values[3] = FieldElement::from_i32(77);
+        assert_eq!(tree.get_root(), MerkleTree::new(values.clone()).get_root());
+
+        // Going from 5 to 9 leaves passes 8, which adds a level
+        for i in 0..4 {
+            tree.push_leaf(FieldElement::from_i32(100 + i));
+            values.push(FieldElement::from_i32(100 + i));
+            let rebuilt = MerkleTree::new(values.clone());
+            assert_eq!(tree.get_root(), rebuilt.get_root());
+            assert_eq!(tree.levels, rebuilt.levels);
+        }
+        assert_eq!(tree.depth(), 4);
+    }
+
+    #[test]
+    fn padding_5_leaves_gives_a_depth_3_tree() {
+        let pad = FieldElement::zero();
+        let tree = MerkleTree::with_padding(leaves(5), pad.clone());
+        assert_eq!(tree.depth(), 3);
+        assert_eq!(tree.leaves.len(), 8);
+        assert_eq!(tree.leaves[5..], [pad.clone(), pad.clone(), pad]);
+        for (index, leaf) in leaves(5).iter().enumerate() {
+            let path = tree.get_proof(index);
+            assert_eq!(path.len(), 3);
+            assert!(tree.verify_proof(leaf, index, &path));
+        }
+        assert_ne!(tree.get_root(), MerkleTree::new(leaves(5)).get_root());
+    }
+
+    #[test]
+    fn multi_proofs_rebuild_the_root_and_share_nodes() {
+        let tree = MerkleTree::new(leaves(8));
+        for indices in [vec![2, 3], vec![1, 4, 6], vec![0, 7]] {
+            let proof = tree.get_multi_proof(&indices);
+            let claimed: Vec<FieldElement> =
+                indices.iter().map(|&i| tree.leaves[i].clone()).collect();
+            assert!(tree.verify_multi_proof(&claimed, &proof), "{:?}", indices);
+
+            let single_proofs = indices.len() * tree.depth();
+            assert!(proof.siblings.len() < single_proofs, "{:?}", indices);
+
+            let mut tampered = claimed.clone();
+            tampered[0] = FieldElement::from_i32(1);
+            assert!(!tree.verify_multi_proof(&tampered, &proof));
+        }
+
+        // Adjacent leaves need no sibling on the first level: one node per level above it
+        assert_eq!(tree.get_multi_proof(&[2, 3]).siblings.len(), 2);
+    }
+
+    #[test]
+    fn serialized_tree_round_trips() {
+        let tree = MerkleTree::new(leaves(5));
+        let restored = MerkleTree::deserialize(&tree.serialize()).unwrap();
+        assert_eq!(restored.get_root(), tree.get_root());
+        assert_eq!(restored.leaves, tree.leaves);
+        assert_eq!(restored.get_proof(3), tree.get_proof(3));
+        assert!(restored.verify_proof(&tree.leaves[3], 3, &tree.get_proof(3)));
+
+        let sha256 = MerkleTree::with_hasher(leaves(3), Box::new(Sha256Hash::new()));
+        let restored =
+            MerkleTree::deserialize_with_hasher(&sha256.serialize(), Box::new(Sha256Hash::new()))
+                .unwrap();
+        assert!(restored.verify_proof(&sha256.leaves[2], 2, &sha256.get_proof(2)));
+
+        let mut corrupted = tree.serialize();
+        corrupted.truncate(corrupted.len() - 1);
+        assert!(MerkleTree::deserialize(&corrupted).is_err());
+    }
+
+    #[test]
+    fn root_only_matches_the_full_tree() {
+        for n in [1, 2, 3, 4, 5, 8, 13] {
+            let expected = MerkleTree::new(leaves(n)).get_root();
+            assert_eq!(
+                MerkleTree::compute_root_only(&leaves(n), &PoseidonHash::new()),
+                expected,
+                "{} leaves",
+                n
+            );
+        }
+    }
+
+    /// Writes `contents` to a fresh file in the system temp directory and returns its path
+    fn leaf_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir()
+            .join(format!("mini_zk_{}_{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned();
+        std::fs::write(&path, contents).unwrap();
         path
     }
+
+    #[test]
+    fn leaves_load_from_a_file() {
+        let path = leaf_file("leaves.txt", "1001\n 2002 \n\n3003\n4004\n");
+        let tree = MerkleTree::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(tree.get_root(), MerkleTree::new(leaves(4)).get_root());
+
+        let path = leaf_file("empty_leaves.txt", "\n  \n");
+        assert!(matches!(
+            MerkleTree::from_file(&path),
+            Err(LeafFileError::Empty)
+        ));
+        std::fs::remove_file(&path).unwrap();
+
+        let path = leaf_file("bad_leaves.txt", "1001\nabc\n");
+        let error = MerkleTree::from_file(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(error, LeafFileError::Parse { line: 2, .. }));
+        assert_eq!(
+            error.to_string(),
+            "line 2: 'abc' is not a valid base-10 field element"
+        );
+    }
+
+    #[test]
+    fn depth_and_capacity_of_full_trees() {
+        for (n, depth) in [(2, 1), (4, 2), (8, 3)] {
+            let tree = MerkleTree::new(leaves(n));
+            assert_eq!(tree.depth(), depth);
+            assert_eq!(tree.capacity(), n as usize);
+        }
+    }
+
+    #[test]
+    fn leaf_iterators_follow_insertion_order() {
+        let input = leaves(5);
+        let tree = MerkleTree::new(input.clone());
+
+        let from_leaves: Vec<FieldElement> = tree.iter_leaves().cloned().collect();
+        assert_eq!(from_leaves, input);
+        let level_zero: Vec<FieldElement> = tree.iter_level(0).unwrap().cloned().collect();
+        assert_eq!(level_zero, input);
+    }
 }
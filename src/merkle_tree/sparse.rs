@@ -0,0 +1,217 @@
+use crate::field::FieldElement;
+use crate::hash_functions::{HashFunction, PoseidonHash};
+use num_bigint::BigInt;
+use num_traits::One;
+use std::collections::HashMap;
+
+/// Depth used by `SparseMerkleTree::new`: enough for every BN128 field element to be its own key
+pub const DEFAULT_DEPTH: usize = 256;
+
+/// A fixed-depth Merkle tree indexed by key, where almost every leaf is empty.
+///
+/// The leaf at position `key` holds `hash(key, value)`, or zero when the key is absent. Empty
+/// subtrees are never stored: their roots are precomputed once per height in `defaults`, so the
+/// tree only keeps the nodes on paths to inserted keys. That makes it possible to prove both
+/// membership (the leaf is `hash(key, value)`) and non-membership (the leaf is empty).
+pub struct SparseMerkleTree {
+    depth: usize,
+    root: FieldElement,
+    defaults: Vec<FieldElement>, // defaults[h] = root of an empty subtree of height h
+    nodes: HashMap<(usize, BigInt), FieldElement>, // Non-empty nodes by (height, index)
+    values: HashMap<BigInt, FieldElement>,
+    hasher: Box<dyn HashFunction>,
+}
+
+impl SparseMerkleTree {
+    /// Creates an empty depth-256 tree hashed with Poseidon
+    pub fn new() -> Self {
+        Self::with_hasher(DEFAULT_DEPTH, Box::new(PoseidonHash::new()))
+    }
+
+    /// Creates an empty tree of the given depth. Keys must be below `2^depth`.
+    pub fn with_hasher(depth: usize, hasher: Box<dyn HashFunction>) -> Self {
+        let mut defaults = vec![FieldElement::zero()];
+        for height in 0..depth {
+            let below = &defaults[height];
+            defaults.push(hasher.hash(below, below));
+        }
+
+        Self {
+            depth,
+            root: defaults[depth].clone(),
+            defaults,
+            nodes: HashMap::new(),
+            values: HashMap::new(),
+            hasher,
+        }
+    }
+
+    pub fn root(&self) -> FieldElement {
+        self.root.clone()
+    }
+
+    pub fn get(&self, key: &FieldElement) -> Option<&FieldElement> {
+        self.values.get(&key.value)
+    }
+
+    /// Sets `key` to `value` (overwriting any previous value) and rehashes the path to the root
+    pub fn insert(&mut self, key: FieldElement, value: FieldElement) {
+        let mut index = self.leaf_index(&key);
+        let mut node = self.hasher.hash(&key, &value);
+        self.values.insert(key.value, value);
+
+        for height in 0..self.depth {
+            let sibling = self.node(height, &(&index ^ BigInt::one()));
+            self.nodes.insert((height, index.clone()), node.clone());
+            node = if index.bit(0) {
+                self.hasher.hash(&sibling, &node)
+            } else {
+                self.hasher.hash(&node, &sibling)
+            };
+            index >>= 1;
+        }
+
+        self.root = node;
+    }
+
+    /// Sibling path (leaf to root) showing `key` is in the tree, or `None` if it is absent
+    pub fn prove_inclusion(&self, key: &FieldElement) -> Option<Vec<FieldElement>> {
+        self.get(key)?;
+        Some(self.siblings(key))
+    }
+
+    /// Sibling path (leaf to root) showing the leaf for `key` is empty, or `None` if `key` is present
+    pub fn prove_non_inclusion(&self, key: &FieldElement) -> Option<Vec<FieldElement>> {
+        if self.get(key).is_some() {
+            return None;
+        }
+        Some(self.siblings(key))
+    }
+
+    /// Checks that `path` proves `key` maps to `value` under the current root
+    pub fn verify_inclusion(
+        &self,
+        key: &FieldElement,
+        value: &FieldElement,
+        path: &[FieldElement],
+    ) -> bool {
+        match self.hasher.try_hash(key, value) {
+            Ok(leaf) => self.root_from_path(key, leaf, path) == Some(self.root.clone()),
+            Err(_) => false,
+        }
+    }
+
+    /// Checks that `path` proves the leaf for `key` is empty under the current root
+    pub fn verify_non_inclusion(&self, key: &FieldElement, path: &[FieldElement]) -> bool {
+        self.root_from_path(key, self.defaults[0].clone(), path) == Some(self.root.clone())
+    }
+
+    fn leaf_index(&self, key: &FieldElement) -> BigInt {
+        assert!(
+            key.value.bits() <= self.depth as u64,
+            "Key {} does not fit in a depth-{} sparse Merkle tree",
+            key,
+            self.depth
+        );
+        key.value.clone()
+    }
+
+    /// The stored node, or the empty-subtree default for that height
+    fn node(&self, height: usize, index: &BigInt) -> FieldElement {
+        self.nodes
+            .get(&(height, index.clone()))
+            .cloned()
+            .unwrap_or_else(|| self.defaults[height].clone())
+    }
+
+    fn siblings(&self, key: &FieldElement) -> Vec<FieldElement> {
+        let mut index = self.leaf_index(key);
+        let mut path = Vec::with_capacity(self.depth);
+        for height in 0..self.depth {
+            path.push(self.node(height, &(&index ^ BigInt::one())));
+            index >>= 1;
+        }
+        path
+    }
+
+    /// Hashes `leaf` up the path, ordering each pair by the bits of `key`.
+    /// `None` if the path has the wrong length, the key doesn't fit the tree or a node can't be hashed.
+    fn root_from_path(
+        &self,
+        key: &FieldElement,
+        leaf: FieldElement,
+        path: &[FieldElement],
+    ) -> Option<FieldElement> {
+        if path.len() != self.depth || key.value.bits() > self.depth as u64 {
+            return None;
+        }
+
+        let mut node = leaf;
+        for (height, sibling) in path.iter().enumerate() {
+            node = if key.value.bit(height as u64) {
+                self.hasher.try_hash(sibling, &node)
+            } else {
+                self.hasher.try_hash(&node, sibling)
+            }
+            .ok()?;
+        }
+        Some(node)
+    }
+}
+
+impl Default for SparseMerkleTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_tree() -> SparseMerkleTree {
+        SparseMerkleTree::with_hasher(16, Box::new(PoseidonHash::new()))
+    }
+
+    #[test]
+    fn inclusion_and_non_inclusion_proofs() {
+        let mut tree = SparseMerkleTree::new();
+        let (key, value) = (FieldElement::from_i32(42), FieldElement::from_i32(7));
+        tree.insert(key.clone(), value.clone());
+
+        let path = tree.prove_inclusion(&key).unwrap();
+        assert_eq!(path.len(), DEFAULT_DEPTH);
+        assert!(tree.verify_inclusion(&key, &value, &path));
+        assert!(!tree.verify_inclusion(&key, &FieldElement::from_i32(8), &path));
+        assert!(tree.prove_non_inclusion(&key).is_none());
+
+        let absent = FieldElement::from_i32(43);
+        assert!(tree.prove_inclusion(&absent).is_none());
+        let path = tree.prove_non_inclusion(&absent).unwrap();
+        assert!(tree.verify_non_inclusion(&absent, &path));
+        assert!(!tree.verify_non_inclusion(&key, &tree.siblings(&key)));
+    }
+
+    #[test]
+    fn updating_a_value_changes_the_root_deterministically() {
+        let (key, other) = (FieldElement::from_i32(5), FieldElement::from_i32(9));
+        let build = |value: i32| {
+            let mut tree = small_tree();
+            tree.insert(other.clone(), FieldElement::from_i32(1));
+            tree.insert(key.clone(), FieldElement::from_i32(value));
+            tree
+        };
+        let empty_root = small_tree().root();
+
+        let mut tree = build(10);
+        let first_root = tree.root();
+        assert_ne!(first_root, empty_root);
+        tree.insert(key.clone(), FieldElement::from_i32(11));
+        assert_ne!(tree.root(), first_root);
+        assert_eq!(tree.root(), build(11).root());
+        assert_eq!(tree.get(&key), Some(&FieldElement::from_i32(11)));
+
+        tree.insert(key.clone(), FieldElement::from_i32(10));
+        assert_eq!(tree.root(), first_root);
+    }
+}
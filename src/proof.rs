@@ -0,0 +1,221 @@
+use crate::circuit::CircuitError;
+use crate::hash_functions::HashError;
+use crate::r1cs::ConstraintError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{Chain, Cursor, Read};
+
+/// First bytes of every proof file
+pub const PROOF_MAGIC: [u8; 4] = *b"MZKP";
+
+/// First bytes of a proof file from `generate_committed_proof`
+pub const COMMITTED_PROOF_MAGIC: [u8; 4] = *b"MZKC";
+
+/// Bumped whenever the proof file layout changes
+pub const PROOF_VERSION: u32 = 1;
+
+/// Written in front of the serialized R1CS so a verifier can tell what it is reading.
+///
+/// The header is written by the prover, so `circuit_digest` proves nothing on its own. It only
+/// catches honest mix-ups early (a proof for another circuit fails with a clear
+/// `ProofError::CircuitMismatch`); soundness comes from `verify_proof` checking the witness
+/// against the verifier's own synthesized constraints.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofHeader {
+    pub magic: [u8; 4],
+    pub version: u32,
+    pub circuit_digest: [u8; 32], // `Circuit::digest` the prover claims; see `ProofHeader` docs
+}
+
+/// A proof stream after `ProofFormat::detect`: any bytes it peeked at, then the rest of the stream
+pub type ProofBody<R> = Chain<Cursor<Vec<u8>>, R>;
+
+/// Every proof file layout this crate has written, oldest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofFormat {
+    V1Bool,          // A bare bincode `bool`: the prover's own verdict, which cannot be verified
+    V2R1cs,          // A bare bincode `R1CS`, written before proofs had a header
+    V3(ProofHeader), // `ProofHeader`, then an `R1CS` or (with `COMMITTED_PROOF_MAGIC`) a `CommittedWitness`
+}
+
+impl ProofFormat {
+    /// Reads just enough of `reader` to tell which layout it holds.
+    /// Returns the format and a reader positioned at the body: past the header for `V3`, at the
+    /// start of the file for the headerless formats (the bytes peeked at are put back in front).
+    /// A headered file with an unknown version is `ProofError::UnsupportedVersion`.
+    pub fn detect<R: Read>(mut reader: R) -> Result<(Self, ProofBody<R>), ProofError> {
+        let mut prefix = Vec::with_capacity(4);
+        (&mut reader).take(4).read_to_end(&mut prefix)?;
+
+        let format = if prefix[..] == PROOF_MAGIC || prefix[..] == COMMITTED_PROOF_MAGIC {
+            let header = read_proof_header(&mut Cursor::new(&prefix).chain(&mut reader))?;
+            prefix.clear();
+            ProofFormat::V3(header)
+        } else if prefix.len() == 4 {
+            // An R1CS starts with its variable count, so its first bytes never spell a magic
+            ProofFormat::V2R1cs
+        } else if prefix.len() == 1 && prefix[0] <= 1 {
+            ProofFormat::V1Bool
+        } else {
+            return Err(ProofError::NotAProof);
+        };
+        Ok((format, Cursor::new(prefix).chain(reader)))
+    }
+}
+
+/// Errors raised while writing or reading a proof file
+#[derive(Debug)]
+pub enum ProofError {
+    Io(std::io::Error),
+    Serialization(bincode::Error),
+    Circuit(CircuitError),
+    Hash(HashError), // A private wire could not be committed to
+    NotAProof,       // The file starts with neither `PROOF_MAGIC` nor `COMMITTED_PROOF_MAGIC`
+    UnsupportedVersion { found: u32, expected: u32 }, // Written by a different proof format version
+    CircuitMismatch, // The proof belongs to a different circuit
+    UnverifiableFormat, // A v1 file: it holds only the prover's claim, with nothing to check
+    Rejected(Rejection), // The proof was read but does not hold (from `verify_and_get_outputs`)
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::Io(e) => write!(f, "proof file I/O failed: {}", e),
+            ProofError::Serialization(e) => write!(f, "proof (de)serialization failed: {}", e),
+            ProofError::Circuit(e) => write!(f, "invalid circuit: {}", e),
+            ProofError::Hash(e) => write!(f, "witness commitment failed: {}", e),
+            ProofError::NotAProof => write!(f, "not a proof file (bad magic bytes)"),
+            ProofError::UnsupportedVersion { found, expected } => write!(
+                f,
+                "proof format version {} is not supported (expected {})",
+                found, expected
+            ),
+            ProofError::CircuitMismatch => {
+                write!(f, "proof was generated for a different circuit")
+            }
+            ProofError::UnverifiableFormat => write!(
+                f,
+                "v1 proof only records the prover's verdict and cannot be verified"
+            ),
+            ProofError::Rejected(rejection) => write!(f, "proof rejected: {}", rejection),
+        }
+    }
+}
+
+impl std::error::Error for ProofError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProofError::Io(e) => Some(e),
+            ProofError::Serialization(e) => Some(e),
+            ProofError::Circuit(e) => Some(e),
+            ProofError::Hash(e) => Some(e),
+            ProofError::Rejected(rejection) => Some(rejection),
+            ProofError::NotAProof
+            | ProofError::UnsupportedVersion { .. }
+            | ProofError::CircuitMismatch
+            | ProofError::UnverifiableFormat => None,
+        }
+    }
+}
+
+impl From<CircuitError> for ProofError {
+    fn from(e: CircuitError) -> Self {
+        ProofError::Circuit(e)
+    }
+}
+
+impl From<HashError> for ProofError {
+    fn from(e: HashError) -> Self {
+        ProofError::Hash(e)
+    }
+}
+
+impl From<std::io::Error> for ProofError {
+    fn from(e: std::io::Error) -> Self {
+        ProofError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for ProofError {
+    fn from(e: bincode::Error) -> Self {
+        ProofError::Serialization(e)
+    }
+}
+
+/// Outcome of checking a proof that could be read
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verification {
+    Valid,
+    Rejected(Rejection),
+    // A committed proof whose public part holds. Its commitments are not opened, so the
+    // `unchecked_constraints` constraints that touch private wires were not checked at all.
+    PartiallyVerified { unchecked_constraints: usize },
+}
+
+impl Verification {
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Verification::Valid)
+    }
+}
+
+/// Why a readable proof does not hold
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    WitnessSize { found: usize, expected: usize }, // Values for a different number of wires than the circuit has
+    PublicInputCount { found: usize, expected: usize }, // Expected values given for a different number of public wires
+    PublicInput { wire: usize }, // A public wire holds something other than the expected value
+    CommitmentCount { found: usize, expected: usize }, // A committed proof misses or adds private wires
+    Constraint(ConstraintError), // The witness breaks one of the circuit's constraints
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::WitnessSize { found, expected } => write!(
+                f,
+                "proof has {} wire values but the circuit has {} wires",
+                found, expected
+            ),
+            Rejection::PublicInputCount { found, expected } => write!(
+                f,
+                "{} public inputs given but the circuit has {} public wires",
+                found, expected
+            ),
+            Rejection::PublicInput { wire } => {
+                write!(f, "public wire {} does not hold the expected value", wire)
+            }
+            Rejection::CommitmentCount { found, expected } => write!(
+                f,
+                "proof commits to {} private wires but the circuit has {}",
+                found, expected
+            ),
+            Rejection::Constraint(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Rejection {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Rejection::Constraint(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a proof file's header, rejecting unknown magic bytes and format versions.
+/// `reader` is left at the start of the body.
+fn read_proof_header<R: Read>(reader: &mut R) -> Result<ProofHeader, ProofError> {
+    let header: ProofHeader =
+        bincode::deserialize_from(reader).map_err(|_| ProofError::NotAProof)?;
+    if header.magic != PROOF_MAGIC && header.magic != COMMITTED_PROOF_MAGIC {
+        return Err(ProofError::NotAProof);
+    }
+    if header.version != PROOF_VERSION {
+        return Err(ProofError::UnsupportedVersion {
+            found: header.version,
+            expected: PROOF_VERSION,
+        });
+    }
+    Ok(header)
+}
@@ -1,20 +1,36 @@
 use crate::field::FieldElement;
+use crate::hash_functions::HashFunction;
+use crate::proof::{PROOF_MAGIC, ProofError, ProofFormat};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
 
-#[derive(Clone, Serialize, Deserialize)]
+/// Variable 0 always holds the constant 1, so constraint terms can carry constant offsets
+/// (`x + 5 = y` is `x * 1 + 1 * 5 = y`). Real inputs start at index 1.
+pub const ONE: usize = 0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Variable {
     pub index: usize,
     pub value: FieldElement,
+    pub label: Option<String>, // Optional human-readable wire name, only used in diagnostics
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Operation {
-    Add,
-    Mul,
-    Hash,
+    Add,  // Linear: `left + right = output`, i.e. `(left + right) * 1 = output` in R1CS form
+    Sub,  // Linear: `left - right = output`, i.e. `(left - right) * 1 = output` in R1CS form
+    Mul,  // `left * right = output`, the only shape that is rank-1 as written
+    Hash, // `hash(left, right) = output`, checked natively; it has no R1CS form here
+    Hash3(Vec<(Variable, FieldElement)>), // `hash(left, right, third) = output`, carrying the third input's terms
+    EqualConstant(FieldElement), // Only `left` is used: it must equal the stored public constant
+    Hash1, // `hash(left) = output` with `right` unused: the hash function's one-input mode
 }
 
-/// A Constraint represents the equation: `(Sum A) * (Sum B) = (Sum C)`
+/// A Constraint relates three weighted sums `A = left`, `B = right` and `C = output`.
+/// How they are combined depends on `operation`: `Mul` is the textbook R1CS row `A * B = C`,
+/// while `Add`/`Sub` are linear rows `A ± B = C`, which R1CS writes as `(A ± B) * 1 = C`.
 ///
 /// **Understanding the Tuple `(Variable, FieldElement)`:**
 /// This tuple represents a single term in an equation, like **"2x"**.
@@ -25,12 +41,13 @@ pub enum Operation {
 /// **Example:**
 /// If you want to represent `3x + 5y`, you would create a generic vector:
 /// `vec![ (x, 3), (y, 5) ]`
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Constraint {
     pub left: Vec<(Variable, FieldElement)>,
     pub right: Vec<(Variable, FieldElement)>,
     pub output: Vec<(Variable, FieldElement)>,
     pub operation: Operation,
+    pub label: Option<String>, // Name of the gate that emitted this constraint, if it was given one
 }
 
 /// The R1CS (Rank-1 Constraint System) is the "World" of the proof.
@@ -40,18 +57,239 @@ pub struct Constraint {
 /// 2. **Memory (Assignments)**: The `variables`. These are the actual values for a specific run.
 ///
 /// If `variables` satisfy all `constraints`, the proof is valid.
+/// `public_inputs` lists the variable indices the verifier knows; every other variable is private witness.
 #[derive(Serialize, Deserialize)]
 pub struct R1CS {
     pub variables: Vec<Variable>,     // The Data (Witness)
     pub constraints: Vec<Constraint>, // The Logic (Circuit itself)
+    pub public_inputs: Vec<usize>,    // Indices into `variables` that are public
+}
+
+/// Size of a constraint system, returned by `R1CS::stats`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct R1csStats {
+    pub num_variables: usize,
+    pub num_constraints: usize,
+    pub add: usize, // Constraints per `Operation` variant
+    pub sub: usize,
+    pub mul: usize,
+    pub hash: usize,
+    pub hash3: usize,
+    pub hash1: usize,
+    pub equal_constant: usize,
+}
+
+impl fmt::Display for R1csStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} variables, {} constraints",
+            self.num_variables, self.num_constraints
+        )?;
+        write!(
+            f,
+            "  add: {}, sub: {}, mul: {}, hash: {}, hash3: {}, hash1: {}, equal_constant: {}",
+            self.add, self.sub, self.mul, self.hash, self.hash3, self.hash1, self.equal_constant
+        )
+    }
 }
 
+/// A circuit's constraints before any witness is bound: `variables` only name the wires (their
+/// values are placeholders). Compile once with `Circuit::synthesize`, then `assign` each witness.
+#[derive(Clone)]
+pub struct ConstraintTemplate {
+    pub variables: Vec<Variable>,
+    pub constraints: Vec<Constraint>,
+    pub public_inputs: Vec<usize>,
+}
+
+impl ConstraintTemplate {
+    /// Binds `witness` (one value per wire, in wire order) to the constraints
+    pub fn assign(&self, witness: &[FieldElement]) -> R1CS {
+        assert_eq!(
+            witness.len(),
+            self.variables.len(),
+            "witness has {} values but the circuit has {} wires",
+            witness.len(),
+            self.variables.len()
+        );
+
+        let bind = |variable: &Variable| Variable {
+            value: witness[variable.index].clone(),
+            ..variable.clone()
+        };
+        let bind_terms = |terms: &[(Variable, FieldElement)]| {
+            terms
+                .iter()
+                .map(|(variable, coeff)| (bind(variable), coeff.clone()))
+                .collect()
+        };
+
+        R1CS {
+            variables: self.variables.iter().map(bind).collect(),
+            constraints: self
+                .constraints
+                .iter()
+                .map(|constraint| Constraint {
+                    left: bind_terms(&constraint.left),
+                    right: bind_terms(&constraint.right),
+                    output: bind_terms(&constraint.output),
+                    operation: match &constraint.operation {
+                        Operation::Hash3(third) => Operation::Hash3(bind_terms(third)),
+                        operation => operation.clone(),
+                    },
+                    label: constraint.label.clone(),
+                })
+                .collect(),
+            public_inputs: self.public_inputs.clone(),
+        }
+    }
+
+    /// Same breakdown as `R1CS::stats`; it does not depend on the witness
+    pub fn stats(&self) -> R1csStats {
+        constraint_stats(self.variables.len(), &self.constraints)
+    }
+}
+
+impl From<R1CS> for ConstraintTemplate {
+    /// Keeps the constraints and wire names; variable values are dropped by the next `assign`
+    fn from(r1cs: R1CS) -> Self {
+        Self {
+            variables: r1cs.variables,
+            constraints: r1cs.constraints,
+            public_inputs: r1cs.public_inputs,
+        }
+    }
+}
+
+/// A constraint whose operation does not hold for the computed sums of its three sides
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnsatisfiedConstraint {
+    pub index: usize,
+    pub label: Option<String>,
+    pub operation: Operation,
+    pub left: FieldElement,
+    pub right: FieldElement,
+    pub output: FieldElement,
+}
+
+/// One constraint reported by `R1CS::all_failures`: its index, operation and computed sums
+pub type ConstraintFailure = UnsatisfiedConstraint;
+
+/// Why `R1CS::check` rejected the witness
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstraintError {
+    // Boxed to keep `Result<(), ConstraintError>` small
+    Unsatisfied(Box<UnsatisfiedConstraint>),
+    // A term refers to a variable index the witness does not have
+    MissingVariable {
+        index: usize,
+        label: Option<String>,
+        variable: usize,
+    },
+    // Variable `ONE` is missing or does not hold 1; not tied to any constraint
+    ConstantOne(Option<FieldElement>),
+}
+
+impl ConstraintError {
+    /// Index of the failing constraint in `R1CS::constraints`, if the failure belongs to one
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            ConstraintError::Unsatisfied(failure) => Some(failure.index),
+            ConstraintError::MissingVariable { index, .. } => Some(*index),
+            ConstraintError::ConstantOne(_) => None,
+        }
+    }
+
+    /// What went wrong, without saying which constraint
+    fn reason(&self) -> String {
+        match self {
+            ConstraintError::Unsatisfied(failure) => {
+                let UnsatisfiedConstraint {
+                    operation,
+                    left,
+                    right,
+                    output,
+                    ..
+                } = failure.as_ref();
+                match operation {
+                    Operation::Add => format!(
+                        "Addition constraint not satisfied: left_value + right_value = {}, while output_value = {}",
+                        left + right,
+                        output
+                    ),
+                    Operation::Sub => format!(
+                        "Subtraction constraint not satisfied: left_value - right_value = {}, while output_value = {}",
+                        left.clone() - right.clone(),
+                        output
+                    ),
+                    Operation::Mul => format!(
+                        "Multiplication constraint not satisfied: left_value * right_value = {}, while output_value = {}",
+                        left * right,
+                        output
+                    ),
+                    Operation::Hash => format!(
+                        "Hash constraint not satisfied: hash({}, {}) does not equal output_value = {}",
+                        left, right, output
+                    ),
+                    Operation::Hash3(_) => format!(
+                        "Hash constraint not satisfied: hash({}, {}, third input) does not equal output_value = {}",
+                        left, right, output
+                    ),
+                    Operation::EqualConstant(constant) => format!(
+                        "Constant constraint not satisfied: value = {}, while expected constant = {}",
+                        left, constant
+                    ),
+                    Operation::Hash1 => format!(
+                        "Hash constraint not satisfied: hash({}) does not equal output_value = {}",
+                        left, output
+                    ),
+                }
+            }
+            ConstraintError::MissingVariable { variable, .. } => format!(
+                "references variable {} that is not in the witness",
+                variable
+            ),
+            ConstraintError::ConstantOne(Some(value)) => {
+                format!("variable {} must be the constant 1, found {}", ONE, value)
+            }
+            ConstraintError::ConstantOne(None) => {
+                format!("variable {} (the constant 1) is missing", ONE)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ConstraintError::Unsatisfied(failure) => &failure.label,
+            ConstraintError::MissingVariable { label, .. } => label,
+            ConstraintError::ConstantOne(_) => return write!(f, "Witness: {}", self.reason()),
+        };
+        if let Some(index) = self.index() {
+            write!(f, "Constraint #{}", index)?;
+        }
+        if let Some(label) = label {
+            write!(f, " [{}]", label)?;
+        }
+        write!(f, ": {}", self.reason())
+    }
+}
+
+impl std::error::Error for ConstraintError {}
+
 impl R1CS {
-    /// Creates a new, empty Constraint System.
+    /// Creates a new Constraint System with no constraints; only variable `ONE` is reserved.
     pub fn new() -> Self {
         Self {
-            variables: Vec::new(),
+            variables: vec![Variable {
+                index: ONE,
+                value: FieldElement::one(),
+                label: Some("one".to_string()),
+            }],
             constraints: Vec::new(),
+            public_inputs: Vec::new(),
         }
     }
 
@@ -71,91 +309,251 @@ impl R1CS {
             right,
             output,
             operation,
+            label: None,
         };
         self.constraints.push(constraint);
     }
 
     /// Adds a known value to the witness memory.
-    /// This is where we store the actual numbers (e.g., "x is 5").
+    /// This is where we store the actual numbers (e.g., "x is 5"). Index 0 is taken by `ONE`.
     pub fn add_variable(&mut self, variable: Variable) {
         self.variables.push(variable);
     }
 
+    /// Appends `other` as an independent system: its variables go after this system's, with every
+    /// index in its constraints and public inputs shifted to match. Both systems share the constant
+    /// `ONE`, so the merged system is satisfied exactly when both originals are.
+    pub fn merge(&mut self, other: R1CS) {
+        let offset = self.variables.len() - 1; // `other`'s variable 1 lands right after ours
+        let shift = |index: usize| if index == ONE { ONE } else { index + offset };
+        let shift_terms = |terms: Vec<(Variable, FieldElement)>| -> Vec<_> {
+            terms
+                .into_iter()
+                .map(|(variable, coeff)| {
+                    let index = shift(variable.index);
+                    (Variable { index, ..variable }, coeff)
+                })
+                .collect()
+        };
+
+        self.variables.extend(
+            other
+                .variables
+                .into_iter()
+                .filter(|variable| variable.index != ONE)
+                .map(|variable| Variable {
+                    index: shift(variable.index),
+                    ..variable
+                }),
+        );
+        self.constraints
+            .extend(other.constraints.into_iter().map(|constraint| Constraint {
+                left: shift_terms(constraint.left),
+                right: shift_terms(constraint.right),
+                output: shift_terms(constraint.output),
+                operation: match constraint.operation {
+                    Operation::Hash3(third) => Operation::Hash3(shift_terms(third)),
+                    operation => operation,
+                },
+                label: constraint.label,
+            }));
+        self.public_inputs
+            .extend(other.public_inputs.into_iter().map(shift));
+    }
+
     /// Verifies that all constraints in the R1CS are satisfied by the current variable assignments.
     ///
     /// For each constraint, this function:
-    /// 1. Computes the weighted sum of the left, right, and output terms
+    /// 1. Computes the weighted sum of the left, right, and output terms.
+    ///    Term values are looked up in `variables` by index, so the witness is the single source of
+    ///    truth (the `Variable` copies embedded in a constraint only identify the wire).
     /// 2. Checks that the operation holds: `left OP right = output`
     ///    - For `Add`: `left + right = output`
+    ///    - For `Sub`: `left - right = output`
     ///    - For `Mul`: `left * right = output`
     ///    - For `Hash`: `hash(left, right) = output`
+    ///    - For `Hash3(third)`: `hash(left, right, third) = output`
+    ///    - For `EqualConstant(c)`: `left = c`
+    ///    - For `Hash1`: `hash(left) = output`
     ///
     /// # Arguments
-    /// * `hash_function` - A closure that computes the hash of two `FieldElement` values
+    /// * `hash_function` - A closure that hashes its inputs (one for `Hash1`, two for `Hash`, three
+    ///   for `Hash3`)
     ///
     /// # Returns
-    /// * `true` if all constraints are satisfied
-    /// * `false` if any constraint fails (prints which constraint type failed)
-    pub fn is_satisfied<K>(&self, hash_function: K) -> bool
+    /// * `Ok(())` if all constraints are satisfied
+    /// * `Err(ConstraintError)` for the first constraint that fails or references a missing variable,
+    ///   carrying its index, operation and computed values, or if variable `ONE` does not hold 1
+    pub fn check<K>(&self, hash_function: K) -> Result<(), ConstraintError>
     where
-        K: Fn(&FieldElement, &FieldElement) -> FieldElement, // Closure to compute hash
+        K: Fn(&[FieldElement]) -> FieldElement, // Closure to compute hash
     {
-        for constraint in &self.constraints {
-            let left_val: FieldElement = constraint
-                .left
-                .iter()
-                .map(|(var, coeff)| &var.value * coeff)
-                .reduce(|a, b| a + b)
-                .unwrap_or_else(|| FieldElement::from_i32(0)); // Start with zero if empty, though usually not empty
-
-            let right_val: FieldElement = constraint
-                .right
-                .iter()
-                .map(|(var, coeff)| &var.value * coeff)
-                .reduce(|a, b| a + b)
-                .unwrap_or_else(|| FieldElement::from_i32(0));
+        self.check_constant_one()?;
+        for (index, constraint) in self.constraints.iter().enumerate() {
+            self.check_constraint(index, constraint, &hash_function)?;
+        }
+        Ok(())
+    }
 
-            let output_val: FieldElement = constraint
-                .output
-                .iter()
-                .map(|(var, coeff)| &var.value * coeff)
-                .reduce(|a, b| a + b)
-                .unwrap_or_else(|| FieldElement::from_i32(0));
-
-            match constraint.operation {
-                Operation::Add => {
-                    if left_val.clone() + right_val.clone() != output_val {
-                        println!(
-                            "Addition constraint not satisfied: left_value + right_value = {}, while output_value = {}",
-                            left_val + right_val,
-                            output_val
-                        );
-                        return false;
-                    }
-                }
-                Operation::Mul => {
-                    if left_val.clone() * right_val.clone() != output_val {
-                        println!(
-                            "Multiplication constraint not satisfied: left_value * right_value = {}, while output_value = {}",
-                            left_val * right_val,
-                            output_val
-                        );
-                        return false;
-                    }
+    /// Like `check`, but keeps going after a failure and returns every constraint that does not
+    /// hold, in constraint order. A term naming a missing variable or a bad constant one makes the
+    /// witness malformed rather than a constraint false; those are left to `check`, so an empty
+    /// vector means the system is satisfied only for a witness `check` does not reject as malformed.
+    pub fn all_failures<K>(&self, hash_function: K) -> Vec<ConstraintFailure>
+    where
+        K: Fn(&[FieldElement]) -> FieldElement,
+    {
+        self.constraints
+            .iter()
+            .enumerate()
+            .filter_map(|(index, constraint)| {
+                match self.check_constraint(index, constraint, &hash_function) {
+                    Err(ConstraintError::Unsatisfied(failure)) => Some(*failure),
+                    _ => None,
                 }
-                Operation::Hash => {
-                    let computed_hash = hash_function(&left_val, &right_val);
-                    if computed_hash != output_val {
-                        println!(
-                            "Hash constraint not satisfied: computed_hash = {}, while output_value = {}",
-                            computed_hash, output_val
-                        );
-                        return false;
+            })
+            .collect()
+    }
+
+    /// Rejects a witness whose variable `ONE` is missing or not 1
+    fn check_constant_one(&self) -> Result<(), ConstraintError> {
+        // A witness that redefines the constant would turn every constant term into a free variable
+        match self.variables.get(ONE) {
+            Some(one) if one.value.is_one() => Ok(()),
+            other => Err(ConstraintError::ConstantOne(
+                other.map(|variable| variable.value.clone()),
+            )),
+        }
+    }
+
+    /// Evaluates constraint `index` against the witness
+    fn check_constraint<K>(
+        &self,
+        index: usize,
+        constraint: &Constraint,
+        hash_function: &K,
+    ) -> Result<(), ConstraintError>
+    where
+        K: Fn(&[FieldElement]) -> FieldElement,
+    {
+        let (left_val, right_val, output_val) = match (
+            weighted_sum(&self.variables, &constraint.left),
+            weighted_sum(&self.variables, &constraint.right),
+            weighted_sum(&self.variables, &constraint.output),
+        ) {
+            (Ok(left), Ok(right), Ok(output)) => (left, right, output),
+            (Err(variable), _, _) | (_, Err(variable), _) | (_, _, Err(variable)) => {
+                return Err(ConstraintError::MissingVariable {
+                    index,
+                    label: constraint.label.clone(),
+                    variable,
+                });
+            }
+        };
+
+        let holds = match &constraint.operation {
+            Operation::Add => &left_val + &right_val == output_val,
+            Operation::Sub => left_val.clone() - right_val.clone() == output_val,
+            Operation::Mul => &left_val * &right_val == output_val,
+            Operation::Hash => hash_function(&[left_val.clone(), right_val.clone()]) == output_val,
+            Operation::Hash3(third) => {
+                let third_val = weighted_sum(&self.variables, third).map_err(|variable| {
+                    ConstraintError::MissingVariable {
+                        index,
+                        label: constraint.label.clone(),
+                        variable,
                     }
+                })?;
+                hash_function(&[left_val.clone(), right_val.clone(), third_val]) == output_val
+            }
+            Operation::EqualConstant(constant) => left_val == *constant,
+            Operation::Hash1 => hash_function(std::slice::from_ref(&left_val)) == output_val,
+        };
+        if !holds {
+            return Err(ConstraintError::Unsatisfied(Box::new(
+                UnsatisfiedConstraint {
+                    index,
+                    label: constraint.label.clone(),
+                    operation: constraint.operation.clone(),
+                    left: left_val,
+                    right: right_val,
+                    output: output_val,
+                },
+            )));
+        }
+        Ok(())
+    }
+
+    /// Boolean form of `check`. On failure it prints the failing constraint's index, label, the
+    /// labels/values of the wires it touches and what went wrong.
+    pub fn is_satisfied<K>(&self, hash_function: K) -> bool
+    where
+        K: Fn(&[FieldElement]) -> FieldElement,
+    {
+        match self.check(&hash_function) {
+            Ok(()) => true,
+            Err(error) => {
+                match error.index() {
+                    Some(index) => println!(
+                        "{}: {}",
+                        self.describe(index, &self.constraints[index]),
+                        error.reason()
+                    ),
+                    None => println!("{}", error),
                 }
+                false
             }
         }
-        true
+    }
+
+    /// Formats `Constraint #i [label]` plus every wire the constraint touches, for failure messages
+    fn describe(&self, index: usize, constraint: &Constraint) -> String {
+        let mut description = format!("Constraint #{}", index);
+        if let Some(label) = &constraint.label {
+            description.push_str(&format!(" [{}]", label));
+        }
+
+        let third: &[(Variable, FieldElement)] = match &constraint.operation {
+            Operation::Hash3(third) => third,
+            _ => &[],
+        };
+        let wires: Vec<String> = constraint
+            .left
+            .iter()
+            .chain(&constraint.right)
+            .chain(third)
+            .chain(&constraint.output)
+            .map(|(var, _)| {
+                let name = match &var.label {
+                    Some(label) => format!("{} (wire {})", label, var.index),
+                    None => format!("wire {}", var.index),
+                };
+                match self.variables.get(var.index) {
+                    Some(witness) => format!("{} = {}", name, witness.value),
+                    None => format!("{} = <missing>", name),
+                }
+            })
+            .collect();
+        if !wires.is_empty() {
+            description.push_str(&format!(" over {}", wires.join(", ")));
+        }
+        description
+    }
+
+    /// Counts variables and constraints, with a breakdown of constraints by operation
+    pub fn stats(&self) -> R1csStats {
+        constraint_stats(self.variables.len(), &self.constraints)
+    }
+
+    /// Human-readable JSON dump of the whole system: variables with their values, constraints with
+    /// their coefficients and operations. Field elements are written as decimal strings.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("R1CS is always representable as JSON")
+    }
+
+    /// Parses a system previously written by `to_json`
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
     }
 
     pub fn save_to_binary(&self, file_name: &str) {
@@ -163,3 +561,274 @@ impl R1CS {
         std::fs::write(file_name, data).expect("Failed to write R1CS to file");
     }
 }
+
+/// Verifies a proof file written by `Circuit::generate_proof` without the `Circuit` that produced it:
+/// the stored constraints and witness are re-checked with `hasher`, and `Ok(true)` means they hold.
+///
+/// Unlike `Circuit::verify_proof` this trusts the constraints stored in the file, so it only shows
+/// that the file is internally consistent: it cannot tell which circuit the file was generated
+/// for, nor check its public inputs against expected values; callers who care must verify
+/// against the `Circuit` instead.
+/// Committed proofs carry no witness to re-check and are rejected as `ProofError::NotAProof`, and
+/// v1 files are `ProofError::UnverifiableFormat`, as in `Circuit::verify_proof`.
+pub fn verify_proof_file(path: &str, hasher: &dyn HashFunction) -> Result<bool, ProofError> {
+    let (format, body) = ProofFormat::detect(BufReader::new(File::open(path)?))?;
+    let r1cs: R1CS = match format {
+        ProofFormat::V1Bool => return Err(ProofError::UnverifiableFormat),
+        ProofFormat::V2R1cs => {
+            bincode::deserialize_from(body).map_err(|_| ProofError::NotAProof)?
+        }
+        ProofFormat::V3(header) if header.magic == PROOF_MAGIC => bincode::deserialize_from(body)?,
+        ProofFormat::V3(_) => return Err(ProofError::NotAProof),
+    };
+    Ok(r1cs.is_satisfied(|inputs| hasher.hash_many(inputs)))
+}
+
+fn constraint_stats(num_variables: usize, constraints: &[Constraint]) -> R1csStats {
+    let mut stats = R1csStats {
+        num_variables,
+        num_constraints: constraints.len(),
+        ..R1csStats::default()
+    };
+    for constraint in constraints {
+        match constraint.operation {
+            Operation::Add => stats.add += 1,
+            Operation::Sub => stats.sub += 1,
+            Operation::Mul => stats.mul += 1,
+            Operation::Hash => stats.hash += 1,
+            Operation::Hash3(_) => stats.hash3 += 1,
+            Operation::Hash1 => stats.hash1 += 1,
+            Operation::EqualConstant(_) => stats.equal_constant += 1,
+        }
+    }
+    stats
+}
+
+/// Computes `Σ coeff * value` over one side of a constraint (zero if the side is empty).
+/// Every product and partial sum goes through `FieldElement` arithmetic and is reduced mod P,
+/// so intermediates stay around 512 bits however many terms the side has.
+/// Returns the offending index if a term points past the end of the witness.
+fn weighted_sum(
+    variables: &[Variable],
+    terms: &[(Variable, FieldElement)],
+) -> Result<FieldElement, usize> {
+    let mut acc = FieldElement::zero();
+    for (var, coeff) in terms {
+        let witness = variables.get(var.index).ok_or(var.index)?;
+        acc += &witness.value * coeff;
+    }
+    Ok(acc)
+}
+
+impl Default for R1CS {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{Circuit, Gate};
+
+    fn fe(value: i64) -> FieldElement {
+        FieldElement::from_i64(value)
+    }
+
+    /// `10 + 20 = sum` with the sum public, `sum` pre-filled so it can be wrong
+    fn addition_system(sum: i64) -> R1CS {
+        let mut circuit = Circuit::new(None);
+        let a = circuit.add_input_named(fe(10), "a");
+        let b = circuit.add_input(fe(20));
+        let sum = circuit.add_public_input(fe(sum));
+        circuit.add_gate_named(Gate::Add(a, b, sum), "sum");
+        circuit.evaluate().unwrap()
+    }
+
+    /// Hash function for systems without hash constraints
+    fn no_hash(_: &[FieldElement]) -> FieldElement {
+        unreachable!("no hash constraints")
+    }
+
+    #[test]
+    fn json_round_trip_reproduces_the_system() {
+        let r1cs = addition_system(30);
+        let json = r1cs.to_json();
+        let parsed = R1CS::from_json(&json).unwrap();
+
+        assert_eq!(parsed.to_json(), json);
+        assert_eq!(parsed.variables, r1cs.variables);
+        assert_eq!(parsed.public_inputs, r1cs.public_inputs);
+        assert_eq!(parsed.constraints.len(), 1);
+        assert_eq!(parsed.constraints[0].operation, Operation::Add);
+        assert_eq!(parsed.constraints[0].label.as_deref(), Some("sum"));
+        assert!(parsed.check(no_hash).is_ok());
+        assert!(
+            json.contains("\"20\""),
+            "field elements are decimal strings"
+        );
+
+        assert!(R1CS::from_json("{").is_err());
+    }
+
+    fn variable(index: usize, value: i64) -> Variable {
+        Variable {
+            index,
+            value: fe(value),
+            label: None,
+        }
+    }
+
+    /// `a * b = c` over fresh variables 1, 2 and 3
+    fn multiplication_system(a: i64, b: i64, c: i64) -> R1CS {
+        let mut r1cs = R1CS::new();
+        for (index, value) in [(1, a), (2, b), (3, c)] {
+            r1cs.add_variable(variable(index, value));
+        }
+        r1cs.add_constraint(
+            vec![(variable(1, a), fe(1))],
+            vec![(variable(2, b), fe(1))],
+            vec![(variable(3, c), fe(1))],
+            Operation::Mul,
+        );
+        r1cs
+    }
+
+    #[test]
+    fn broken_multiplication_reports_index_and_values() {
+        let mut r1cs = multiplication_system(3, 4, 12);
+        assert!(r1cs.check(no_hash).is_ok());
+        r1cs.add_constraint(
+            vec![(variable(1, 3), fe(1))],
+            vec![(variable(2, 4), fe(2))],
+            vec![(variable(3, 12), fe(1))],
+            Operation::Mul,
+        );
+
+        let error = r1cs.check(no_hash).unwrap_err();
+        assert_eq!(error.index(), Some(1));
+        assert_eq!(
+            error,
+            ConstraintError::Unsatisfied(Box::new(UnsatisfiedConstraint {
+                index: 1,
+                label: None,
+                operation: Operation::Mul,
+                left: fe(3),
+                right: fe(8),
+                output: fe(12),
+            }))
+        );
+        assert!(error.to_string().contains("= 24, while output_value = 12"));
+        assert!(!r1cs.is_satisfied(no_hash));
+    }
+
+    #[test]
+    fn merged_system_holds_iff_both_originals_hold() {
+        for (sum, product) in [(30, 12), (31, 12), (30, 13), (31, 13)] {
+            let both_hold = sum == 30 && product == 12;
+            let mut merged = addition_system(sum);
+            let n_variables = merged.variables.len();
+            merged.merge(multiplication_system(3, 4, product));
+
+            assert_eq!(merged.variables.len(), n_variables + 3);
+            assert_eq!(merged.constraints.len(), 2);
+            assert!(
+                merged
+                    .variables
+                    .iter()
+                    .enumerate()
+                    .all(|(index, variable)| variable.index == index)
+            );
+            assert_eq!(
+                merged.check(no_hash).is_ok(),
+                both_hold,
+                "{} {}",
+                sum,
+                product
+            );
+        }
+    }
+
+    #[test]
+    fn circuit_proof_verifies_without_the_circuit() {
+        use crate::hash_functions::{PoseidonHash, Sha256Hash};
+
+        let mut circuit = Circuit::new(Some(Box::new(PoseidonHash::new())));
+        let a = circuit.add_input(fe(1));
+        let b = circuit.add_input(fe(2));
+        let digest = circuit.alloc_wire();
+        circuit.add_gate(Gate::Hash(a, b, digest));
+        let path = std::env::temp_dir()
+            .join(format!("mini_zk_{}_standalone.bin", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        assert!(circuit.generate_proof(&path).unwrap());
+
+        assert!(verify_proof_file(&path, &PoseidonHash::new()).unwrap());
+        assert!(!verify_proof_file(&path, &Sha256Hash::new()).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn long_linear_combination_stays_reduced_and_fast() {
+        // 500 terms of (P - 1) * (P - 1) = 1, so the sum is 500
+        let n = 500;
+        let minus_one = -FieldElement::one();
+        let mut r1cs = R1CS::new();
+        let mut terms = Vec::new();
+        for index in 1..=n {
+            let variable = Variable {
+                index,
+                value: minus_one.clone(),
+                label: None,
+            };
+            r1cs.add_variable(variable.clone());
+            terms.push((variable, minus_one.clone()));
+        }
+        let output = variable(n + 1, 500);
+        r1cs.add_variable(output.clone());
+        r1cs.add_constraint(terms, vec![], vec![(output, fe(1))], Operation::Add);
+
+        let start = std::time::Instant::now();
+        assert!(r1cs.check(no_hash).is_ok());
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+
+        r1cs.variables[n + 1].value = fe(499);
+        assert!(r1cs.check(no_hash).is_err());
+    }
+
+    #[test]
+    fn all_failures_reports_every_broken_constraint() {
+        // Constraint 0 holds (2 * 3 = 6); 1 (2 * 2 = 6) and 2 (3 * 3 = 6) do not
+        let mut r1cs = multiplication_system(2, 3, 6);
+        for (left, right) in [
+            (variable(1, 2), variable(1, 2)),
+            (variable(2, 3), variable(2, 3)),
+        ] {
+            r1cs.add_constraint(
+                vec![(left, fe(1))],
+                vec![(right, fe(1))],
+                vec![(variable(3, 6), fe(1))],
+                Operation::Mul,
+            );
+        }
+
+        let failures = r1cs.all_failures(no_hash);
+        let indices: Vec<usize> = failures.iter().map(|failure| failure.index).collect();
+        assert_eq!(indices, [1, 2]);
+        assert!(
+            failures
+                .iter()
+                .all(|failure| failure.operation == Operation::Mul)
+        );
+        assert_eq!(
+            (&failures[0].left, &failures[0].right, &failures[0].output),
+            (&fe(2), &fe(2), &fe(6))
+        );
+        assert_eq!(
+            (&failures[1].left, &failures[1].right, &failures[1].output),
+            (&fe(3), &fe(3), &fe(6))
+        );
+        assert!(!r1cs.is_satisfied(no_hash));
+    }
+}
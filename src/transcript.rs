@@ -0,0 +1,69 @@
+use crate::field::FieldElement;
+use crate::hash_functions::PoseidonHash;
+
+/// Public transcript for the Fiat–Shamir transform: everything the verifier would have seen is
+/// absorbed in order, and challenges are derived from it instead of coming from the verifier.
+///
+/// Each element is appended as its canonical 32-byte encoding, and a challenge is
+/// `PoseidonHash::hash_bytes` of everything absorbed so far. The challenge is then absorbed itself,
+/// so consecutive challenges differ and each one commits to all earlier ones.
+pub struct Transcript {
+    data: Vec<u8>,
+    hasher: PoseidonHash,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            hasher: PoseidonHash::new(),
+        }
+    }
+
+    /// Appends `value` to the transcript
+    pub fn absorb(&mut self, value: &FieldElement) {
+        self.data.extend_from_slice(&value.to_bytes_le());
+    }
+
+    /// Derives the next challenge from everything absorbed so far
+    pub fn challenge(&mut self) -> FieldElement {
+        let challenge = self.hasher.hash_bytes(&self.data);
+        self.absorb(&challenge);
+        challenge
+    }
+}
+
+impl Default for Transcript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge_after(values: &[i32]) -> FieldElement {
+        let mut transcript = Transcript::new();
+        for value in values {
+            transcript.absorb(&FieldElement::from_i32(*value));
+        }
+        transcript.challenge()
+    }
+
+    #[test]
+    fn same_sequence_gives_the_same_challenge() {
+        assert_eq!(challenge_after(&[1, 2, 3]), challenge_after(&[1, 2, 3]));
+        assert_ne!(challenge_after(&[1, 2, 3]), challenge_after(&[1, 2, 4]));
+    }
+
+    #[test]
+    fn order_of_absorption_matters() {
+        assert_ne!(challenge_after(&[1, 2]), challenge_after(&[2, 1]));
+
+        let mut transcript = Transcript::new();
+        transcript.absorb(&FieldElement::from_i32(1));
+        let first = transcript.challenge();
+        assert_ne!(transcript.challenge(), first);
+    }
+}